@@ -1,23 +1,11 @@
-# TCL File Generated by Component Editor 20.1
-# Thu Nov 12 22:55:09 PST 2020
-# DO NOT MODIFY
+# Component description for `runNetworkTop`.
+# Generated from the Rust register map `point_nn`. DO NOT MODIFY.
 
-
-# 
-# runNetworkAVS "runNetworkAVS" v1.0
-#  2020.11.12.22:55:09
-# 
-# 
-
-# 
-# request TCL package from ACDS 16.1
-# 
 package require -exact qsys 16.1
 
-
-# 
+#
 # module runNetworkAVS
-# 
+#
 set_module_property DESCRIPTION ""
 set_module_property NAME runNetworkAVS
 set_module_property VERSION 1.0
@@ -31,10 +19,9 @@ set_module_property REPORT_TO_TALKBACK false
 set_module_property ALLOW_GREYBOX_GENERATION false
 set_module_property REPORT_HIERARCHY false
 
-
-# 
+#
 # file sets
-# 
+#
 add_fileset QUARTUS_SYNTH QUARTUS_SYNTH "" ""
 set_fileset_property QUARTUS_SYNTH TOP_LEVEL runNetworkTop
 set_fileset_property QUARTUS_SYNTH ENABLE_RELATIVE_INCLUDE_PATHS false
@@ -42,20 +29,9 @@ set_fileset_property QUARTUS_SYNTH ENABLE_FILE_OVERWRITE_MODE false
 add_fileset_file runNetwork.v VERILOG PATH runNetwork.v
 add_fileset_file runNetworkTop.sv SYSTEM_VERILOG PATH runNetworkTop.sv TOP_LEVEL_FILE
 
-
-# 
-# parameters
-# 
-
-
-# 
-# display items
-# 
-
-
-# 
+#
 # connection point clock
-# 
+#
 add_interface clock clock end
 set_interface_property clock clockRate 0
 set_interface_property clock ENABLED true
@@ -66,10 +42,9 @@ set_interface_property clock SVD_ADDRESS_GROUP ""
 
 add_interface_port clock clk clk Input 1
 
-
-# 
+#
 # connection point reset
-# 
+#
 add_interface reset reset end
 set_interface_property reset associatedClock clock
 set_interface_property reset synchronousEdges DEASSERT
@@ -81,10 +56,9 @@ set_interface_property reset SVD_ADDRESS_GROUP ""
 
 add_interface_port reset reset reset Input 1
 
-
-# 
+#
 # connection point s0
-# 
+#
 add_interface s0 avalon end
 set_interface_property s0 addressUnits WORDS
 set_interface_property s0 associatedClock clock
@@ -117,4 +91,3 @@ set_interface_assignment s0 embeddedsw.configuration.isFlash 0
 set_interface_assignment s0 embeddedsw.configuration.isMemoryDevice 0
 set_interface_assignment s0 embeddedsw.configuration.isNonVolatileStorage 0
 set_interface_assignment s0 embeddedsw.configuration.isPrintableDevice 0
-
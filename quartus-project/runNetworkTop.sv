@@ -1,10 +1,11 @@
-/* Run the 2D neural network at once
+/* Avalon-MM slave wrapper for `runNetwork`.
+ * Generated from the Rust register map `point_nn`. DO NOT MODIFY.
  */
- 
+
 module runNetworkTop(
   input  logic        clk,
   input  logic        reset,
-  
+
   // Memory mapped read/write slave interface
   input  logic  [3:0] avs_s0_address,  // avs_s0.address
   input  logic        avs_s0_read,     // avs_s0.read
@@ -12,56 +13,44 @@ module runNetworkTop(
   output logic [31:0] avs_s0_readdata, // avs_s0.readdata
   input  logic [31:0] avs_s0_writedata // avs_s0.writedata
 );
- 
-logic [31:0] a, b;
-wire [63:0] in; 
-assign in = {a, b};
 
-logic [31:0] c, c_d;
+// Register `in` (read/write, words 0-1)
+logic [31:0] in_w0, in_w1;
+wire [63:0] in;
+assign in = {in_w0, in_w1};
+
+// Register `out` (read-only, word 2)
 wire [31:0] out;
-assign c = out;
+logic [31:0] out_d;
 
-runNetwork network (.in (in), .out (out));
+runNetwork core (.in (in), .out (out));
 
 always_ff @(posedge clk) begin
-    c_d <= c;
+    out_d <= out;
 end
 
-// AXI Write Controller
+// Avalon write controller
 always_ff @(posedge clk) begin
     if (reset) begin
-        a <= 0;
-        b <= 0;
+        in_w0 <= 0;
+        in_w1 <= 0;
     end
     else if (avs_s0_write) begin
         case (avs_s0_address)
-            3'd0 : begin
-                a <= avs_s0_writedata;
-                b <= b;
-            end
-            3'd1 : begin
-                a <= a;
-                b <= avs_s0_writedata;
-            end
-            default: begin
-                a <= a;
-                b <= b;
-            end
+            4'd0 : in_w0 <= avs_s0_writedata;
+            4'd1 : in_w1 <= avs_s0_writedata;
+            default: ;
         endcase
     end
-    else begin
-        a <= a;
-        b <= b;
-    end
-end    
+end
 
-//AXI Slave Read Controller
+// Avalon read controller
 always_comb begin
     if (avs_s0_read) begin
         case (avs_s0_address)
-            3'd0 : avs_s0_readdata = a;
-            3'd1 : avs_s0_readdata = b;
-            3'd2 : avs_s0_readdata = c_d;
+            4'd0 : avs_s0_readdata = in_w0;
+            4'd1 : avs_s0_readdata = in_w1;
+            4'd2 : avs_s0_readdata = out_d;
             default: avs_s0_readdata = 0;
         endcase
     end
@@ -69,5 +58,5 @@ always_comb begin
         avs_s0_readdata = 'x;
     end
 end
-           
-endmodule
\ No newline at end of file
+
+endmodule
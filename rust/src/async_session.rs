@@ -0,0 +1,362 @@
+//! Async FPGA session API for use from Tokio.
+//!
+//! `ThreadedSesh` runs any blocking `Session` on a dedicated thread so that
+//! executor threads never block on the bus, and `SimAsyncSesh` is an
+//! async-native simulated register block.
+
+use std::future::Future;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use tokio::sync::{oneshot, RwLock};
+
+use crate::poll::{Backoff, PollError, Poller, Step};
+use crate::session::{read_bytes, write_bytes};
+use crate::traits::{Readable, Session, Writable};
+use crate::{FpgaApiError, FpgaApiResult};
+
+/// Async counterpart of `traits::Session`. Resources are cloned into the
+/// request, so they must be cheap to clone and own their data.
+pub trait AsyncSession: Sync {
+    /// Read a readable resource.
+    fn read<R>(&self, resource: &R) -> impl Future<Output = FpgaApiResult<R::Value>> + Send
+    where
+        R: Readable + Clone + Send + Sync + 'static,
+        R::Value: Send + 'static;
+    /// Write to a writable resource.
+    fn write<R>(
+        &self,
+        resource: &R,
+        val: R::Value,
+    ) -> impl Future<Output = FpgaApiResult<()>> + Send
+    where
+        R: Writable + Clone + Send + Sync + 'static,
+        R::Value: Send + 'static;
+    /// Read `resource` until `done` holds for its value, sleeping on the
+    /// Tokio timer between reads. See `poll::wait_until`.
+    fn wait_until<R, F>(
+        &self,
+        resource: &R,
+        mut done: F,
+        timeout: Duration,
+        backoff: Backoff,
+    ) -> impl Future<Output = Result<R::Value, PollError<R::Value>>> + Send
+    where
+        R: Readable + Clone + Send + Sync + 'static,
+        R::Value: Send + 'static,
+        F: FnMut(&R::Value) -> bool + Send,
+    {
+        async move {
+            let mut poller = Poller::new(timeout, backoff);
+            loop {
+                let val = self.read(resource).await.map_err(PollError::Session)?;
+                let done = done(&val);
+                match poller.step(val, done) {
+                    Step::Done(val) => return Ok(val),
+                    Step::TimedOut(e) => return Err(e),
+                    Step::Retry(delay) if delay > Duration::from_secs(0) => {
+                        tokio::time::sleep(delay).await
+                    }
+                    Step::Retry(_) => tokio::task::yield_now().await,
+                }
+            }
+        }
+    }
+}
+
+type Job<S> = Box<dyn FnOnce(&mut S) + Send>;
+
+/// Runs a blocking session on a dedicated thread.
+///
+/// Dropping the handle closes the request queue without waiting: the thread
+/// finishes the pending requests and drops the session in the background,
+/// so dropping inside async code never blocks an executor thread. Await
+/// `shutdown` to know when the session (and its teardown) is gone.
+pub struct ThreadedSesh<S: Session + Send + 'static> {
+    jobs: Option<mpsc::Sender<Job<S>>>,
+    stopped: Option<oneshot::Receiver<()>>,
+}
+impl<S: Session + Send + 'static> ThreadedSesh<S> {
+    pub fn new(mut sesh: S) -> FpgaApiResult<Self> {
+        let (jobs, rx) = mpsc::channel::<Job<S>>();
+        let (stopped_tx, stopped) = oneshot::channel();
+        // The thread is detached; `stopped` reports its end instead of a join.
+        std::thread::Builder::new()
+            .name("fpga-session".to_string())
+            .spawn(move || {
+                for job in rx {
+                    job(&mut sesh);
+                }
+                drop(sesh);
+                let _ = stopped_tx.send(());
+            })?;
+        Ok(Self {
+            jobs: Some(jobs),
+            stopped: Some(stopped),
+        })
+    }
+    /// Stop accepting requests and wait until the pending ones are done and
+    /// the session has been dropped on its thread. Fails if a request
+    /// panicked and took the thread down.
+    pub async fn shutdown(mut self) -> FpgaApiResult<()> {
+        self.jobs.take();
+        match self.stopped.take() {
+            Some(stopped) => stopped
+                .await
+                .map_err(|_| FpgaApiError::from("FPGA session thread panicked!")),
+            None => Ok(()),
+        }
+    }
+    /// Queue `f` on the session thread. The request is sent before the
+    /// returned future is first polled, so requests run in call order.
+    fn call<T, F>(&self, f: F) -> impl Future<Output = FpgaApiResult<T>> + Send + 'static
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut S) -> FpgaApiResult<T> + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let job: Job<S> = Box::new(move |sesh| {
+            let _ = tx.send(f(sesh));
+        });
+        let sent = self.jobs.as_ref().is_some_and(|j| j.send(job).is_ok());
+        async move {
+            let stopped = || FpgaApiError::from("FPGA session thread has stopped!");
+            if !sent {
+                return Err(stopped());
+            }
+            rx.await.map_err(|_| stopped())?
+        }
+    }
+}
+impl<S: Session + Send + 'static> AsyncSession for ThreadedSesh<S> {
+    fn read<R>(&self, resource: &R) -> impl Future<Output = FpgaApiResult<R::Value>> + Send
+    where
+        R: Readable + Clone + Send + Sync + 'static,
+        R::Value: Send + 'static,
+    {
+        let resource = resource.clone();
+        self.call(move |sesh| sesh.read(&resource))
+    }
+    fn write<R>(
+        &self,
+        resource: &R,
+        val: R::Value,
+    ) -> impl Future<Output = FpgaApiResult<()>> + Send
+    where
+        R: Writable + Clone + Send + Sync + 'static,
+        R::Value: Send + 'static,
+    {
+        let resource = resource.clone();
+        self.call(move |sesh| sesh.write(&resource, val))
+    }
+}
+impl<S: Session + Send + 'static> Drop for ThreadedSesh<S> {
+    fn drop(&mut self) {
+        // Closing the channel ends the worker loop, which drops the session.
+        self.jobs.take();
+    }
+}
+
+/// Async-native simulated register block, optionally with a bus latency per
+/// access.
+pub struct SimAsyncSesh {
+    mem: RwLock<Vec<u8>>,
+    latency: Duration,
+}
+impl SimAsyncSesh {
+    /// Zero-initialized register block of `len` bytes.
+    pub fn new(len: usize) -> Self {
+        Self {
+            mem: RwLock::new(vec![0; len]),
+            latency: Duration::from_secs(0),
+        }
+    }
+    /// Sleep for `latency` on every access.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+    /// Raw register block, e.g. to emulate hardware updating registers.
+    pub fn memory(&self) -> &RwLock<Vec<u8>> {
+        &self.mem
+    }
+    async fn bus_delay(&self) {
+        if self.latency > Duration::from_secs(0) {
+            tokio::time::sleep(self.latency).await;
+        }
+    }
+}
+impl AsyncSession for SimAsyncSesh {
+    async fn read<R>(&self, resource: &R) -> FpgaApiResult<R::Value>
+    where
+        R: Readable + Clone + Send + Sync + 'static,
+        R::Value: Send + 'static,
+    {
+        self.bus_delay().await;
+        read_bytes(&self.mem.read().await, resource)
+    }
+    async fn write<R>(&self, resource: &R, val: R::Value) -> FpgaApiResult<()>
+    where
+        R: Writable + Clone + Send + Sync + 'static,
+        R::Value: Send + 'static,
+    {
+        self.bus_delay().await;
+        write_bytes(&mut self.mem.write().await, resource, val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use crate::resources::Resource;
+    use crate::session::SimSesh;
+    use crate::traits::ReadWrite;
+
+    fn reg(offset: usize) -> Resource<u32, ReadWrite> {
+        Resource::new("reg", offset)
+    }
+
+    /// Simulated register block that flags when it is dropped and can hold
+    /// its first read until released.
+    struct Probe {
+        sim: SimSesh,
+        dropped: Arc<AtomicBool>,
+        gate: Option<mpsc::Receiver<()>>,
+    }
+    impl Session for Probe {
+        fn read<R: Readable>(&self, resource: &R) -> FpgaApiResult<R::Value> {
+            if let Some(gate) = &self.gate {
+                let _ = gate.recv();
+            }
+            self.sim.read(resource)
+        }
+        fn write<R: Writable>(&mut self, resource: &R, val: R::Value) -> FpgaApiResult<()> {
+            self.sim.write(resource, val)
+        }
+    }
+    impl Drop for Probe {
+        fn drop(&mut self) {
+            self.dropped.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn probe(gate: Option<mpsc::Receiver<()>>) -> (Probe, Arc<AtomicBool>) {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let probe = Probe {
+            sim: SimSesh::new(8),
+            dropped: dropped.clone(),
+            gate,
+        };
+        (probe, dropped)
+    }
+
+    #[tokio::test]
+    async fn threaded_requests_run_in_call_order() {
+        let sesh = ThreadedSesh::new(SimSesh::new(8)).unwrap();
+        let r = reg(4);
+        for i in 0..100 {
+            // Neither future is polled before both requests are queued.
+            let write = sesh.write(&r, i);
+            let read = sesh.read(&r);
+            let (written, read) = tokio::join!(write, read);
+            written.unwrap();
+            assert_eq!(read.unwrap(), i);
+        }
+        assert_eq!(sesh.read(&reg(0)).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn threaded_errors_come_back_to_the_caller() {
+        let sesh = ThreadedSesh::new(SimSesh::new(8)).unwrap();
+        assert!(sesh.write(&reg(8), 1).await.is_err());
+        sesh.write(&reg(0), 1).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_the_session_to_drop() {
+        let (probe, dropped) = probe(None);
+        let sesh = ThreadedSesh::new(probe).unwrap();
+        sesh.write(&reg(0), 7).await.unwrap();
+        sesh.shutdown().await.unwrap();
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn drop_does_not_wait_for_pending_requests() {
+        let (release, gate) = mpsc::channel();
+        let (probe, dropped) = probe(Some(gate));
+        let sesh = ThreadedSesh::new(probe).unwrap();
+        // Queued but held on the session thread until released. `call`
+        // keeps the future independent of the handle.
+        let read = sesh.call(|s| s.read(&reg(0)));
+        drop(sesh);
+        assert!(!dropped.load(Ordering::SeqCst));
+        release.send(()).unwrap();
+        assert_eq!(read.await.unwrap(), 0);
+        while !dropped.load(Ordering::SeqCst) {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sim_latency_applies_to_every_access() {
+        let latency = Duration::from_millis(5);
+        let sesh = SimAsyncSesh::new(8).with_latency(latency);
+        let start = tokio::time::Instant::now();
+        sesh.write(&reg(0), 3).await.unwrap();
+        assert_eq!(start.elapsed(), latency);
+        assert_eq!(sesh.read(&reg(0)).await.unwrap(), 3);
+        assert_eq!(start.elapsed(), 2 * latency);
+    }
+
+    #[tokio::test]
+    async fn wait_until_returns_once_the_register_changes() {
+        let sesh = Arc::new(SimAsyncSesh::new(8));
+        let hw = sesh.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            hw.memory().write().await[0] = 1;
+        });
+        let val = sesh
+            .wait_until(
+                &reg(0),
+                |v| *v == 1,
+                Duration::from_secs(10),
+                Backoff::Fixed(Duration::from_millis(1)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(val, 1);
+    }
+
+    #[tokio::test]
+    async fn wait_until_times_out_with_the_last_value() {
+        let sesh = SimAsyncSesh::new(8);
+        sesh.write(&reg(0), 9).await.unwrap();
+        let timeout = Duration::from_millis(20);
+        match sesh
+            .wait_until(
+                &reg(0),
+                |v| *v == 1,
+                timeout,
+                Backoff::Fixed(Duration::from_millis(1)),
+            )
+            .await
+        {
+            Err(PollError::Timeout {
+                last,
+                elapsed,
+                reads,
+            }) => {
+                assert_eq!(last, 9);
+                assert!(elapsed >= timeout);
+                assert!(reads > 1);
+            }
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+    }
+}
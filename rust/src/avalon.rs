@@ -0,0 +1,518 @@
+//! SystemVerilog Avalon-MM wrappers generated from a `RegisterMap`.
+//!
+//! The wrapper instantiates the core and connects every register to the core
+//! port of the same name, like the hand-written `runNetworkTop.sv` did:
+//!
+//! * writable registers are flip-flops loaded from the bus, one per word,
+//!   and drive a core input;
+//! * read-only registers capture a core output one cycle late;
+//! * readable registers are decoded onto `avs_s0_readdata`.
+//!
+//! Register `r` connects to a port of `8 * r.size` bits. The words of a
+//! vector register are concatenated with element 0 in the most significant
+//! bits (the layout of a Clash `Vec`); the words of a scalar are little
+//! endian, matching the Rust side. A Platform Designer `_hw.tcl` describing
+//! the component is generated alongside.
+
+use std::fmt::Write;
+
+use crate::regmap::{Access, Register, RegisterMap, WORD_SIZE};
+use crate::FpgaApiResult;
+
+const WORD_BITS: usize = 8 * WORD_SIZE;
+
+/// Names and files of a generated wrapper.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AvalonWrapper<'a> {
+    map: &'a RegisterMap,
+    core: String,
+    module: String,
+    component: String,
+    address_width: usize,
+}
+impl<'a> AvalonWrapper<'a> {
+    /// Wrapper around the core module `core` (e.g. the Clash `t_name`),
+    /// named `<core>Top`, in the component `<core>AVS`.
+    pub fn new(map: &'a RegisterMap, core: &str) -> Self {
+        let words = map.words().max(2);
+        Self {
+            map,
+            core: core.to_string(),
+            module: format!("{}Top", core),
+            component: format!("{}AVS", core),
+            address_width: (usize::BITS - (words - 1).leading_zeros()) as usize,
+        }
+    }
+    /// Name of the wrapper module.
+    pub fn module(mut self, module: &str) -> Self {
+        self.module = module.to_string();
+        self
+    }
+    /// Name of the Platform Designer component.
+    pub fn component(mut self, component: &str) -> Self {
+        self.component = component.to_string();
+        self
+    }
+    /// Width of the word address port, if wider than the map needs (e.g. to
+    /// keep an existing system's interface).
+    pub fn address_width(mut self, width: usize) -> Self {
+        self.address_width = self.address_width.max(width);
+        self
+    }
+    /// File name of the generated SystemVerilog.
+    pub fn sv_file(&self) -> String {
+        format!("{}.sv", self.module)
+    }
+    /// File name of the generated component description.
+    pub fn hw_tcl_file(&self) -> String {
+        format!("{}_hw.tcl", self.component)
+    }
+
+    /// The wrapper module.
+    pub fn systemverilog(&self) -> FpgaApiResult<String> {
+        self.map.check()?;
+        let aw = self.address_width;
+        let mut s = String::new();
+        let w = &mut s;
+        writeln!(w, "/* Avalon-MM slave wrapper for `{}`.", self.core)?;
+        writeln!(
+            w,
+            " * Generated from the Rust register map `{}`. DO NOT MODIFY.",
+            self.map.name
+        )?;
+        writeln!(w, " */")?;
+        writeln!(w)?;
+        writeln!(w, "module {}(", self.module)?;
+        writeln!(w, "  input  logic        clk,")?;
+        writeln!(w, "  input  logic        reset,")?;
+        writeln!(w)?;
+        writeln!(w, "  // Memory mapped read/write slave interface")?;
+        let addr = format!("[{}:0]", aw - 1);
+        writeln!(
+            w,
+            "  input  logic {:>6} avs_s0_address,  // avs_s0.address",
+            addr
+        )?;
+        writeln!(w, "  input  logic        avs_s0_read,     // avs_s0.read")?;
+        writeln!(w, "  input  logic        avs_s0_write,    // avs_s0.write")?;
+        writeln!(
+            w,
+            "  output logic [31:0] avs_s0_readdata, // avs_s0.readdata"
+        )?;
+        writeln!(
+            w,
+            "  input  logic [31:0] avs_s0_writedata // avs_s0.writedata"
+        )?;
+        writeln!(w, ");")?;
+
+        for r in self.map.registers {
+            let bits = 8 * r.size;
+            let words = r.words();
+            writeln!(w)?;
+            writeln!(
+                w,
+                "// Register `{}` ({}, {})",
+                r.name,
+                access_name(r.access),
+                word_range(r)
+            )?;
+            if r.access.writable() {
+                let regs: Vec<_> = (0..words).map(|k| word_reg(r, k)).collect();
+                writeln!(w, "logic [31:0] {};", regs.join(", "))?;
+                writeln!(w, "wire [{}:0] {};", bits - 1, r.name)?;
+                let concat = concat(r);
+                if bits == words * WORD_BITS {
+                    writeln!(w, "assign {} = {};", r.name, concat)?;
+                } else {
+                    writeln!(w, "wire [{}:0] {}_words;", words * WORD_BITS - 1, r.name)?;
+                    writeln!(w, "assign {}_words = {};", r.name, concat)?;
+                    writeln!(w, "assign {} = {}_words[{}:0];", r.name, r.name, bits - 1)?;
+                }
+            } else {
+                writeln!(w, "wire [{}:0] {};", bits - 1, r.name)?;
+                writeln!(w, "logic [{}:0] {}_d;", words * WORD_BITS - 1, r.name)?;
+            }
+        }
+
+        writeln!(w)?;
+        let ports: Vec<_> = self
+            .map
+            .registers
+            .iter()
+            .map(|r| format!(".{} ({})", r.name, r.name))
+            .collect();
+        writeln!(w, "{} core ({});", self.core, ports.join(", "))?;
+
+        let outputs: Vec<_> = self
+            .map
+            .registers
+            .iter()
+            .filter(|r| !r.access.writable())
+            .collect();
+        if !outputs.is_empty() {
+            writeln!(w)?;
+            writeln!(w, "always_ff @(posedge clk) begin")?;
+            for r in outputs {
+                let pad = r.words() * WORD_BITS - 8 * r.size;
+                if pad == 0 {
+                    writeln!(w, "    {}_d <= {};", r.name, r.name)?;
+                } else {
+                    writeln!(w, "    {}_d <= {{{}'d0, {}}};", r.name, pad, r.name)?;
+                }
+            }
+            writeln!(w, "end")?;
+        }
+
+        writeln!(w)?;
+        writeln!(w, "// Avalon write controller")?;
+        writeln!(w, "always_ff @(posedge clk) begin")?;
+        writeln!(w, "    if (reset) begin")?;
+        for r in self.writable() {
+            for k in 0..r.words() {
+                writeln!(w, "        {} <= 0;", word_reg(r, k))?;
+            }
+        }
+        writeln!(w, "    end")?;
+        writeln!(w, "    else if (avs_s0_write) begin")?;
+        writeln!(w, "        case (avs_s0_address)")?;
+        for r in self.writable() {
+            for k in 0..r.words() {
+                writeln!(
+                    w,
+                    "            {}'d{} : {} <= avs_s0_writedata;",
+                    aw,
+                    r.word_offset() + k,
+                    word_reg(r, k)
+                )?;
+            }
+        }
+        writeln!(w, "            default: ;")?;
+        writeln!(w, "        endcase")?;
+        writeln!(w, "    end")?;
+        writeln!(w, "end")?;
+
+        writeln!(w)?;
+        writeln!(w, "// Avalon read controller")?;
+        writeln!(w, "always_comb begin")?;
+        writeln!(w, "    if (avs_s0_read) begin")?;
+        writeln!(w, "        case (avs_s0_address)")?;
+        for r in self.map.registers.iter().filter(|r| r.access.readable()) {
+            for k in 0..r.words() {
+                let src = if r.access.writable() {
+                    word_reg(r, k)
+                } else {
+                    output_word(r, k)
+                };
+                writeln!(
+                    w,
+                    "            {}'d{} : avs_s0_readdata = {};",
+                    aw,
+                    r.word_offset() + k,
+                    src
+                )?;
+            }
+        }
+        writeln!(w, "            default: avs_s0_readdata = 0;")?;
+        writeln!(w, "        endcase")?;
+        writeln!(w, "    end")?;
+        writeln!(w, "    else begin")?;
+        writeln!(w, "        avs_s0_readdata = 'x;")?;
+        writeln!(w, "    end")?;
+        writeln!(w, "end")?;
+        writeln!(w)?;
+        writeln!(w, "endmodule")?;
+        Ok(s)
+    }
+
+    fn writable(&self) -> impl Iterator<Item = &Register> {
+        self.map.registers.iter().filter(|r| r.access.writable())
+    }
+
+    /// The Platform Designer component description.
+    pub fn hw_tcl(&self) -> FpgaApiResult<String> {
+        self.map.check()?;
+        let mut s = String::new();
+        let w = &mut s;
+        writeln!(w, "# Component description for `{}`.", self.module)?;
+        writeln!(
+            w,
+            "# Generated from the Rust register map `{}`. DO NOT MODIFY.",
+            self.map.name
+        )?;
+        writeln!(w)?;
+        writeln!(w, "package require -exact qsys 16.1")?;
+        writeln!(w)?;
+        writeln!(w, "#")?;
+        writeln!(w, "# module {}", self.component)?;
+        writeln!(w, "#")?;
+        for (key, value) in [
+            ("DESCRIPTION", "\"\"".to_string()),
+            ("NAME", self.component.clone()),
+            ("VERSION", "1.0".to_string()),
+            ("INTERNAL", "false".to_string()),
+            ("OPAQUE_ADDRESS_MAP", "true".to_string()),
+            ("AUTHOR", "\"\"".to_string()),
+            ("DISPLAY_NAME", self.component.clone()),
+            ("INSTANTIATE_IN_SYSTEM_MODULE", "true".to_string()),
+            ("EDITABLE", "true".to_string()),
+            ("REPORT_TO_TALKBACK", "false".to_string()),
+            ("ALLOW_GREYBOX_GENERATION", "false".to_string()),
+            ("REPORT_HIERARCHY", "false".to_string()),
+        ] {
+            writeln!(w, "set_module_property {} {}", key, value)?;
+        }
+        writeln!(w)?;
+        writeln!(w, "#")?;
+        writeln!(w, "# file sets")?;
+        writeln!(w, "#")?;
+        writeln!(w, "add_fileset QUARTUS_SYNTH QUARTUS_SYNTH \"\" \"\"")?;
+        writeln!(
+            w,
+            "set_fileset_property QUARTUS_SYNTH TOP_LEVEL {}",
+            self.module
+        )?;
+        writeln!(
+            w,
+            "set_fileset_property QUARTUS_SYNTH ENABLE_RELATIVE_INCLUDE_PATHS false"
+        )?;
+        writeln!(
+            w,
+            "set_fileset_property QUARTUS_SYNTH ENABLE_FILE_OVERWRITE_MODE false"
+        )?;
+        writeln!(
+            w,
+            "add_fileset_file {}.v VERILOG PATH {}.v",
+            self.core, self.core
+        )?;
+        writeln!(
+            w,
+            "add_fileset_file {} SYSTEM_VERILOG PATH {} TOP_LEVEL_FILE",
+            self.sv_file(),
+            self.sv_file()
+        )?;
+        writeln!(w)?;
+        for (name, kind, port, role) in [
+            ("clock", "clock", "clk", "clk"),
+            ("reset", "reset", "reset", "reset"),
+        ] {
+            writeln!(w, "#")?;
+            writeln!(w, "# connection point {}", name)?;
+            writeln!(w, "#")?;
+            writeln!(w, "add_interface {} {} end", name, kind)?;
+            if name == "clock" {
+                writeln!(w, "set_interface_property clock clockRate 0")?;
+            } else {
+                writeln!(w, "set_interface_property reset associatedClock clock")?;
+                writeln!(w, "set_interface_property reset synchronousEdges DEASSERT")?;
+            }
+            writeln!(w, "set_interface_property {} ENABLED true", name)?;
+            write_default_interface_properties(w, name)?;
+            writeln!(w)?;
+            writeln!(w, "add_interface_port {} {} {} Input 1", name, port, role)?;
+            writeln!(w)?;
+        }
+        writeln!(w, "#")?;
+        writeln!(w, "# connection point s0")?;
+        writeln!(w, "#")?;
+        writeln!(w, "add_interface s0 avalon end")?;
+        for (key, value) in [
+            ("addressUnits", "WORDS"),
+            ("associatedClock", "clock"),
+            ("associatedReset", "reset"),
+            ("bitsPerSymbol", "8"),
+            ("burstOnBurstBoundariesOnly", "false"),
+            ("burstcountUnits", "WORDS"),
+            ("explicitAddressSpan", "0"),
+            ("holdTime", "0"),
+            ("linewrapBursts", "false"),
+            ("maximumPendingReadTransactions", "0"),
+            ("maximumPendingWriteTransactions", "0"),
+            ("readLatency", "0"),
+            ("readWaitTime", "1"),
+            ("setupTime", "0"),
+            ("timingUnits", "Cycles"),
+            ("writeWaitTime", "0"),
+            ("ENABLED", "true"),
+        ] {
+            writeln!(w, "set_interface_property s0 {} {}", key, value)?;
+        }
+        write_default_interface_properties(w, "s0")?;
+        writeln!(w)?;
+        writeln!(
+            w,
+            "add_interface_port s0 avs_s0_address address Input {}",
+            self.address_width
+        )?;
+        writeln!(w, "add_interface_port s0 avs_s0_read read Input 1")?;
+        writeln!(w, "add_interface_port s0 avs_s0_write write Input 1")?;
+        writeln!(
+            w,
+            "add_interface_port s0 avs_s0_readdata readdata Output 32"
+        )?;
+        writeln!(
+            w,
+            "add_interface_port s0 avs_s0_writedata writedata Input 32"
+        )?;
+        for key in [
+            "isFlash",
+            "isMemoryDevice",
+            "isNonVolatileStorage",
+            "isPrintableDevice",
+        ] {
+            writeln!(
+                w,
+                "set_interface_assignment s0 embeddedsw.configuration.{} 0",
+                key
+            )?;
+        }
+        Ok(s)
+    }
+}
+
+/// Properties the Component Editor writes for every interface.
+fn write_default_interface_properties(w: &mut String, name: &str) -> std::fmt::Result {
+    for key in [
+        "EXPORT_OF",
+        "PORT_NAME_MAP",
+        "CMSIS_SVD_VARIABLES",
+        "SVD_ADDRESS_GROUP",
+    ] {
+        writeln!(w, "set_interface_property {} {} \"\"", name, key)?;
+    }
+    Ok(())
+}
+
+fn access_name(access: Access) -> &'static str {
+    match access {
+        Access::ReadOnly => "read-only",
+        Access::ReadWrite => "read/write",
+        Access::WriteOnly => "write-only",
+    }
+}
+
+fn word_range(r: &Register) -> String {
+    match r.words() {
+        1 => format!("word {}", r.word_offset()),
+        n => format!("words {}-{}", r.word_offset(), r.word_offset() + n - 1),
+    }
+}
+
+fn word_reg(r: &Register, k: usize) -> String {
+    format!("{}_w{}", r.name, k)
+}
+
+/// Concatenation of a writable register's words into its port value.
+fn concat(r: &Register) -> String {
+    let mut regs: Vec<_> = (0..r.words()).map(|k| word_reg(r, k)).collect();
+    if r.elements == 1 {
+        // Scalars are little endian: word 0 holds the low bits.
+        regs.reverse();
+    }
+    if regs.len() == 1 {
+        regs.remove(0)
+    } else {
+        format!("{{{}}}", regs.join(", "))
+    }
+}
+
+/// Word `k` of a read-only register's captured value.
+fn output_word(r: &Register, k: usize) -> String {
+    let n = r.words();
+    if n == 1 {
+        return format!("{}_d", r.name);
+    }
+    let lsb = if r.elements == 1 {
+        k * WORD_BITS
+    } else {
+        (n - 1 - k) * WORD_BITS
+    };
+    format!("{}_d[{}:{}]", r.name, lsb + WORD_BITS - 1, lsb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn quartus_file(name: &str) -> String {
+        std::fs::read_to_string(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("../quartus-project")
+                .join(name),
+        )
+        .unwrap()
+    }
+
+    const THREE_WORDS: RegisterMap = RegisterMap::new(
+        "three",
+        &[
+            Register::new("a", 0, 8, Access::ReadWrite),
+            Register::new("b", 8, 2, Access::ReadOnly),
+        ],
+    );
+
+    #[test]
+    fn checked_in_point_nn_wrapper_is_up_to_date() {
+        // As built by the `point_nn_wrapper` binary.
+        let wrapper =
+            AvalonWrapper::new(&crate::POINT_NN_REGISTER_MAP, "runNetwork").address_width(4);
+        assert_eq!(wrapper.sv_file(), "runNetworkTop.sv");
+        assert_eq!(wrapper.hw_tcl_file(), "runNetworkAVS_hw.tcl");
+        assert_eq!(
+            wrapper.systemverilog().unwrap(),
+            quartus_file(&wrapper.sv_file())
+        );
+        assert_eq!(
+            wrapper.hw_tcl().unwrap(),
+            quartus_file(&wrapper.hw_tcl_file())
+        );
+    }
+
+    #[test]
+    fn address_width_covers_the_map() {
+        let wrapper = AvalonWrapper::new(&THREE_WORDS, "core");
+        assert_eq!(wrapper.address_width, 2);
+        assert_eq!(wrapper.clone().address_width(1).address_width, 2);
+        assert_eq!(wrapper.address_width(5).address_width, 5);
+        const ONE_WORD: RegisterMap =
+            RegisterMap::new("one", &[Register::new("a", 0, 4, Access::ReadWrite)]);
+        assert_eq!(AvalonWrapper::new(&ONE_WORD, "core").address_width, 1);
+    }
+
+    #[test]
+    fn names_can_be_overridden() {
+        let wrapper = AvalonWrapper::new(&THREE_WORDS, "core")
+            .module("wrap")
+            .component("comp");
+        assert_eq!(wrapper.sv_file(), "wrap.sv");
+        assert_eq!(wrapper.hw_tcl_file(), "comp_hw.tcl");
+        assert!(wrapper.hw_tcl().unwrap().contains("TOP_LEVEL wrap\n"));
+    }
+
+    #[test]
+    fn narrow_outputs_are_zero_padded() {
+        let sv = AvalonWrapper::new(&THREE_WORDS, "core")
+            .systemverilog()
+            .unwrap();
+        assert!(sv.contains("wire [15:0] b;\nlogic [31:0] b_d;\n"));
+        assert!(sv.contains("    b_d <= {16'd0, b};\n"));
+        assert!(sv.contains("            2'd2 : avs_s0_readdata = b_d;\n"));
+        // `a` is a little-endian scalar: word 0 holds the low bits.
+        assert!(sv.contains("assign a = {a_w1, a_w0};\n"));
+        assert!(sv.contains("            2'd1 : avs_s0_readdata = a_w1;\n"));
+    }
+
+    #[test]
+    fn invalid_maps_are_rejected() {
+        const OVERLAP: RegisterMap = RegisterMap::new(
+            "overlap",
+            &[
+                Register::new("a", 0, 8, Access::ReadWrite),
+                Register::new("b", 4, 4, Access::ReadOnly),
+            ],
+        );
+        let wrapper = AvalonWrapper::new(&OVERLAP, "core");
+        assert!(wrapper.systemverilog().is_err());
+        assert!(wrapper.hw_tcl().is_err());
+    }
+}
@@ -0,0 +1,41 @@
+//! Generate a Rust register map from a Clash entity.
+//!
+//! Usage: `clash_regmap <clash-manifest.json> <Entity.hs | signature> [crate]`
+//!
+//! The second argument is either the Haskell source defining `topEntity` or
+//! its type signature, e.g. `"Vec 2 (SFixed 7 25) -> Vec 1 (SFixed 7 25)"`.
+//! The generated module is written to stdout and refers to this library as
+//! `crate` (default `sbtb`).
+
+use std::path::Path;
+
+use sbtb::clash::{find_signature, generate, Manifest};
+use sbtb::FpgaApiResult;
+
+fn run() -> FpgaApiResult<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() < 2 || args.len() > 3 {
+        return Err(
+            "Usage: clash_regmap <clash-manifest.json> <Entity.hs | signature> [crate]".into(),
+        );
+    }
+    let manifest = Manifest::from_json(&std::fs::read_to_string(&args[0])?)?;
+    let signature = if Path::new(&args[1]).is_file() {
+        find_signature(&std::fs::read_to_string(&args[1])?)?
+    } else {
+        args[1].clone()
+    };
+    let krate = args.get(2).map_or("sbtb", String::as_str);
+    print!("{}", generate(&manifest, &signature, krate)?);
+    Ok(())
+}
+
+fn main() {
+    std::process::exit(match run() {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            1
+        }
+    });
+}
@@ -2,48 +2,56 @@
 
 use rand::prelude::*;
 
-use sbtb::resources::Resource;
-use sbtb::traits::{ReadOnly, ReadWrite, Session};
-use sbtb::{
-    take_fpga_session, FpgaApiResult, POINT_NN_INPUT_VECTOR_OFFSET, POINT_NN_OUTPUT_CLASS_OFFSET,
-};
-
-use fixed::types::I7F25;
+use sbtb::fpga_manager::FpgaManager;
+use sbtb::peripherals::{Peripherals, PointNn};
+use sbtb::point_nn::{Conversion, Point};
+use sbtb::traits::Session;
+use sbtb::FpgaApiResult;
 
 const NUM_POINTS: usize = 500;
 
 fn run() -> FpgaApiResult<()> {
-    // Get the FPGA singleton. Better not try and do this more than once!
-    let mut sesh = take_fpga_session();
+    // Take the ownership tokens of the classifier and its registers.
+    let PointNn {
+        core,
+        input_vector: input_point,
+        output_class,
+    } = Peripherals::take()
+        .ok_or("Peripherals have already been taken!")?
+        .point_nn;
+    // The bitstream is programmed at boot; check that the FPGA is running.
+    let loaded = FpgaManager::new().already_loaded()?;
+    let mut sesh = core.open(&loaded)?;
 
-    // Make some data.
+    // Make some data. Points outside of the I7F25 range are saturated, which
+    // keeps them in the same quadrant.
     let mut rng = rand::thread_rng();
-    let mut point_vec: Vec<(I7F25, I7F25)> = Vec::with_capacity(NUM_POINTS);
+    let mut point_vec: Vec<Point> = Vec::with_capacity(NUM_POINTS);
+    let mut num_saturated = 0;
     for _ in 0..NUM_POINTS {
         let (x, y): (f32, f32) = (rng.gen_range(-126.0, 126.0), rng.gen_range(-126.0, 126.0));
-        let (x_fx, y_fx) = (I7F25::from_num(x), I7F25::from_num(y));
-        point_vec.push((x_fx, y_fx));
+        if Point::out_of_range(x, y).is_some() {
+            num_saturated += 1;
+        }
+        point_vec.push(Point::from_f32(x, y, Conversion::Saturate)?);
+    }
+    if num_saturated > 0 {
+        eprintln!(
+            "WARNING: saturated {} of {} points outside of the I7F25 range",
+            num_saturated, NUM_POINTS
+        );
     }
-
-    // Define the resources.
-    let input_point =
-        Resource::<(I7F25, I7F25), ReadWrite>::new("Input Points", POINT_NN_INPUT_VECTOR_OFFSET);
-    let output_class = Resource::<I7F25, ReadOnly>::new(
-        "Output Classification Register",
-        POINT_NN_OUTPUT_CLASS_OFFSET,
-    );
 
     // Classify the points using the FPGA and write to CSV.
     let mut wtr = csv::Writer::from_path("fpga_classified_points.csv")?;
     // Write header.
-    wtr.write_record(&["x", "y", "class"])?;
+    wtr.write_record(["x", "y", "class"])?;
     for point in point_vec {
         sesh.write(&input_point, point)?;
         let classification = sesh.read(&output_class)?;
-        let (x, y) = point;
         wtr.serialize((
-            x.to_num::<f32>(),
-            y.to_num::<f32>(),
+            point.x.to_num::<f32>(),
+            point.y.to_num::<f32>(),
             classification.to_num::<f32>(),
         ))?;
     }
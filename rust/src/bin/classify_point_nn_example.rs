@@ -1,35 +1,39 @@
 //! Interact with FPGA quadrant classifier.
 
-use sbtb::resources::Resource;
-use sbtb::traits::{ReadOnly, ReadWrite, Session};
-use sbtb::{
-    take_fpga_session, FpgaApiResult, POINT_NN_INPUT_VECTOR_OFFSET, POINT_NN_OUTPUT_CLASS_OFFSET,
-};
+use sbtb::fpga_manager::FpgaManager;
+use sbtb::peripherals::{Peripherals, PointNn};
+use sbtb::point_nn::{Conversion, Point};
+use sbtb::traits::Session;
+use sbtb::FpgaApiResult;
 
 use fixed::types::I7F25;
 
 fn run() -> FpgaApiResult<()> {
-    // Get the FPGA singleton. Better not try and do this more than once!
-    let mut sesh = take_fpga_session();
+    // Take the ownership tokens. A second `take` returns `None`, and each
+    // token can only be moved to a single owner.
+    let PointNn {
+        core,
+        input_vector: input_point,
+        output_class,
+    } = Peripherals::take()
+        .ok_or("Peripherals have already been taken!")?
+        .point_nn;
+    // The bitstream is programmed at boot; check that the FPGA is running.
+    let loaded = FpgaManager::new().already_loaded()?;
+    let mut sesh = core.open(&loaded)?;
 
-    // Define the resources.
-    let input_point =
-        Resource::<(I7F25, I7F25), ReadWrite>::new("Input Points", POINT_NN_INPUT_VECTOR_OFFSET);
-    let output_class = Resource::<I7F25, ReadOnly>::new(
-        "Output Classification Register",
-        POINT_NN_OUTPUT_CLASS_OFFSET,
-    );
-
-    // Locations (Fixed point 7/25 values).
-    let (pos_x, neg_x) = (I7F25::from_num(1.5), I7F25::from_num(-1.5));
-    let (pos_y, neg_y) = (I7F25::from_num(2.5), I7F25::from_num(-2.5));
+    // Locations (Fixed point 7/25 values), rejected if out of range.
+    let q1_point = Point::from_f32(1.5, 2.5, Conversion::Error)?;
+    let q2_point = Point::from_f32(-1.5, 2.5, Conversion::Error)?;
+    let q3_point = Point::from_f32(-1.5, -2.5, Conversion::Error)?;
+    let q4_point = Point::from_f32(1.5, -2.5, Conversion::Error)?;
 
     // Do some FPGA stuff!
     // Quadrant 1.
     println!("\nQuadrant 1");
     println!("==========\n");
-    println!("Writing ({}, {}) to {}", pos_x, pos_y, &input_point);
-    sesh.write(&input_point, (pos_x, pos_y))?;
+    println!("Writing {} to {}", q1_point, &input_point);
+    sesh.write(&input_point, q1_point)?;
     println!("Reading result from {}", &output_class);
     let q1_actual = sesh.read(&output_class)?;
     let q1_expected = I7F25::from_num(1.0);
@@ -39,8 +43,8 @@ fn run() -> FpgaApiResult<()> {
     // Quadrant 2.
     println!("\nQuadrant 2");
     println!("==========\n");
-    println!("Writing ({}, {}) to {}", neg_x, pos_y, &input_point);
-    sesh.write(&input_point, (neg_x, pos_y))?;
+    println!("Writing {} to {}", q2_point, &input_point);
+    sesh.write(&input_point, q2_point)?;
     println!("Reading result from {}", &output_class);
     let q2_actual = sesh.read(&output_class)?;
     let q2_expected = I7F25::from_num(-1.0);
@@ -50,8 +54,8 @@ fn run() -> FpgaApiResult<()> {
     // Quadrant 3.
     println!("\nQuadrant 3");
     println!("==========\n");
-    println!("Writing ({}, {}) to {}", neg_x, neg_y, &input_point);
-    sesh.write(&input_point, (neg_x, neg_y))?;
+    println!("Writing {} to {}", q3_point, &input_point);
+    sesh.write(&input_point, q3_point)?;
     println!("Reading result from {}", &output_class);
     let q3_actual = sesh.read(&output_class)?;
     let q3_expected = q1_expected;
@@ -61,8 +65,8 @@ fn run() -> FpgaApiResult<()> {
     // Quadrant 4.
     println!("\nQuadrant 4");
     println!("==========\n");
-    println!("Writing ({}, {}) to {}", pos_x, neg_y, &input_point);
-    sesh.write(&input_point, (pos_x, neg_y))?;
+    println!("Writing {} to {}", q4_point, &input_point);
+    sesh.write(&input_point, q4_point)?;
     println!("Reading result from {}", &output_class);
     let q4_actual = sesh.read(&output_class)?;
     let q4_expected = q2_expected;
@@ -0,0 +1,32 @@
+//! Generate the point classifier's Avalon wrapper and component description
+//! from `POINT_NN_REGISTER_MAP`.
+//!
+//! Usage: `point_nn_wrapper [output directory]` (default `../quartus-project`)
+
+use std::path::PathBuf;
+
+use sbtb::avalon::AvalonWrapper;
+use sbtb::{FpgaApiResult, POINT_NN_REGISTER_MAP};
+
+fn run() -> FpgaApiResult<()> {
+    let dir = PathBuf::from(
+        std::env::args()
+            .nth(1)
+            .unwrap_or_else(|| "../quartus-project".to_string()),
+    );
+    // Keep the 4-bit address port of the existing Platform Designer system.
+    let wrapper = AvalonWrapper::new(&POINT_NN_REGISTER_MAP, "runNetwork").address_width(4);
+    std::fs::write(dir.join(wrapper.sv_file()), wrapper.systemverilog()?)?;
+    std::fs::write(dir.join(wrapper.hw_tcl_file()), wrapper.hw_tcl()?)?;
+    Ok(())
+}
+
+fn main() {
+    std::process::exit(match run() {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            1
+        }
+    });
+}
@@ -0,0 +1,119 @@
+//! Resources branded with the session they belong to.
+//!
+//! `brand` runs a closure with a session tagged by a fresh lifetime `'id`
+//! that no other session shares. Resources registered with that session
+//! carry the same brand, so using them with another session, or outside of
+//! the closure (after the session is gone), fails to compile.
+//!
+//! ```
+//! use sbtb::brand::brand;
+//! use sbtb::region::Region;
+//! use sbtb::resources::Resource;
+//! use sbtb::session::SimSesh;
+//! use sbtb::traits::ReadWrite;
+//!
+//! const SIM: Region = Region::new("sim", 0, 4);
+//!
+//! let val = brand(SimSesh::new(4), |mut sesh| {
+//!     let reg = sesh.resource(Resource::<u32, ReadWrite>::in_region(&SIM, "reg", 0)?);
+//!     sesh.write(&reg, 7)?;
+//!     sesh.read(&reg)
+//! });
+//! assert_eq!(val.unwrap(), 7);
+//! ```
+//!
+//! A resource branded by one session cannot be used with another:
+//!
+//! ```compile_fail
+//! # use sbtb::brand::brand;
+//! # use sbtb::region::Region;
+//! # use sbtb::resources::Resource;
+//! # use sbtb::session::SimSesh;
+//! # use sbtb::traits::ReadWrite;
+//! # const SIM: Region = Region::new("sim", 0, 4);
+//! brand(SimSesh::new(4), |a| {
+//!     brand(SimSesh::new(4), |b| {
+//!         let reg = a.resource(Resource::<u32, ReadWrite>::in_region(&SIM, "reg", 0)?);
+//!         b.read(&reg)
+//!     })
+//! });
+//! ```
+//!
+//! nor leave the closure that created it:
+//!
+//! ```compile_fail
+//! # use sbtb::brand::brand;
+//! # use sbtb::region::Region;
+//! # use sbtb::resources::Resource;
+//! # use sbtb::session::SimSesh;
+//! # use sbtb::traits::ReadWrite;
+//! # const SIM: Region = Region::new("sim", 0, 4);
+//! let reg = brand(SimSesh::new(4), |sesh| {
+//!     sesh.resource(Resource::<u32, ReadWrite>::in_region(&SIM, "reg", 0).unwrap())
+//! });
+//! ```
+
+use core::marker::PhantomData;
+
+use crate::traits::{Readable, Session, Writable};
+use crate::FpgaApiResult;
+
+/// Invariant lifetime, so that two brands never unify.
+type Id<'id> = PhantomData<fn(&'id ()) -> &'id ()>;
+
+/// Run `f` with `sesh` under a fresh brand. The session can be taken back out
+/// with `BrandedSesh::into_inner`; branded resources cannot.
+pub fn brand<S, T, F>(sesh: S, f: F) -> T
+where
+    S: Session,
+    F: for<'id> FnOnce(BrandedSesh<'id, S>) -> T,
+{
+    f(BrandedSesh {
+        sesh,
+        _id: PhantomData,
+    })
+}
+
+/// Session tagged with the brand `'id`.
+pub struct BrandedSesh<'id, S: Session> {
+    sesh: S,
+    _id: Id<'id>,
+}
+impl<'id, S: Session> BrandedSesh<'id, S> {
+    /// Tie a resource to this session.
+    pub fn resource<R>(&self, resource: R) -> BrandedResource<'id, R> {
+        BrandedResource {
+            resource,
+            _id: PhantomData,
+        }
+    }
+    /// Read a resource branded by this session.
+    pub fn read<R: Readable>(&self, resource: &BrandedResource<'id, R>) -> FpgaApiResult<R::Value> {
+        self.sesh.read(&resource.resource)
+    }
+    /// Write to a resource branded by this session.
+    pub fn write<R: Writable>(
+        &mut self,
+        resource: &BrandedResource<'id, R>,
+        val: R::Value,
+    ) -> FpgaApiResult<()> {
+        self.sesh.write(&resource.resource, val)
+    }
+    /// Give up the brand and return the session.
+    pub fn into_inner(self) -> S {
+        self.sesh
+    }
+}
+
+/// Resource that can only be used with the session branded `'id`. The
+/// underlying resource is not exposed, since it could be copied out of the
+/// brand.
+pub struct BrandedResource<'id, R> {
+    resource: R,
+    _id: Id<'id>,
+}
+impl<R: core::fmt::Display> core::fmt::Display for BrandedResource<'_, R> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.resource.fmt(f)
+    }
+}
@@ -0,0 +1,586 @@
+//! Rust register maps generated from Clash entities.
+//!
+//! Clash's JSON manifest (`clash-manifest.json`) lists the flattened HDL
+//! ports of the top entity with their widths, but not their Clash types. The
+//! generator pairs each port with the matching argument (or result) of the
+//! `topEntity` type signature, checks that the widths agree, and emits Rust
+//! source with a `Data` type per port, one `Resource` per port, and a
+//! `RegisterMap`. Inputs become read/write registers (the wrapper reads them
+//! back) and outputs read-only registers, laid out word by word in port
+//! order.
+//!
+//! Supported Clash types are `Bool`, `Bit`, `Signed n`, `Unsigned n`,
+//! `BitVector n`, `SFixed i f` and `UFixed i f` of 8, 16, 32 or 64 bits, and
+//! `Vec n` of 32-bit scalars, with element `i` in bus word `i` like in
+//! `runNetworkTop.sv`. `crate::data` implements `Data` for all of them.
+//!
+//! `src/run_network.rs` is generated from `quartus-project/clash-manifest.json`
+//! and `ip/RunNetwork.hs`; a test keeps it up to date.
+
+use std::fmt::Write;
+
+use serde::Deserialize;
+
+use crate::regmap::WORD_SIZE;
+use crate::{FpgaApiError, FpgaApiResult};
+
+/// The parts of a Clash manifest the generator uses.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Manifest {
+    pub top_component: Component,
+}
+impl Manifest {
+    pub fn from_json(json: &str) -> FpgaApiResult<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Component {
+    pub name: String,
+    pub ports_flat: Vec<Port>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Port {
+    pub name: String,
+    pub direction: String,
+    pub width: usize,
+    #[serde(default)]
+    pub is_clock: bool,
+    #[serde(default)]
+    pub type_name: String,
+}
+
+/// A scalar Clash type with a Rust counterpart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scalar {
+    Bool,
+    Signed(usize),
+    Unsigned(usize),
+    SFixed(usize, usize),
+    UFixed(usize, usize),
+}
+impl Scalar {
+    /// Width in bits on the Clash side.
+    pub fn bits(self) -> usize {
+        match self {
+            Scalar::Bool => 1,
+            Scalar::Signed(n) | Scalar::Unsigned(n) => n,
+            Scalar::SFixed(i, f) | Scalar::UFixed(i, f) => i + f,
+        }
+    }
+    /// Rust type implementing `Data`.
+    pub fn rust_type(self) -> String {
+        match self {
+            Scalar::Bool => "bool".to_string(),
+            Scalar::Signed(n) => format!("i{}", n),
+            Scalar::Unsigned(n) => format!("u{}", n),
+            Scalar::SFixed(i, f) => format!("fixed::types::I{}F{}", i, f),
+            Scalar::UFixed(i, f) => format!("fixed::types::U{}F{}", i, f),
+        }
+    }
+    fn check(self) -> FpgaApiResult<Self> {
+        match self {
+            Scalar::Bool => Ok(self),
+            _ if [8, 16, 32, 64].contains(&self.bits()) => Ok(self),
+            _ => Err(FpgaApiError::from(format!(
+                "{:?} is not 8, 16, 32 or 64 bits wide",
+                self
+            ))),
+        }
+    }
+}
+
+/// A port type: a scalar or a vector of scalars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortType {
+    pub len: Option<usize>,
+    pub elem: Scalar,
+}
+impl PortType {
+    /// Parse a Clash type such as `Vec 2 (SFixed 7 25)`.
+    pub fn parse(ty: &str) -> FpgaApiResult<Self> {
+        let words = tokens(ty);
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        match words.as_slice() {
+            ["Vec", n, rest @ ..] => {
+                let elem = scalar(&rest.join(" "))?;
+                if elem.bits() != 32 {
+                    return Err(FpgaApiError::from(format!(
+                        "Vector elements must be 32 bits wide, not {:?}",
+                        elem
+                    )));
+                }
+                Ok(Self {
+                    len: Some(number(n)?),
+                    elem,
+                })
+            }
+            _ => Ok(Self {
+                len: None,
+                elem: scalar(ty)?,
+            }),
+        }
+    }
+    pub fn bits(&self) -> usize {
+        self.len.unwrap_or(1) * self.elem.bits()
+    }
+}
+
+/// Split at top-level spaces, dropping one level of parentheses per word.
+fn tokens(ty: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut depth = 0;
+    let mut cur = String::new();
+    for c in ty.trim().chars() {
+        match c {
+            '(' if depth == 0 => depth += 1,
+            ')' if depth == 1 => depth -= 1,
+            '(' => {
+                depth += 1;
+                cur.push(c);
+            }
+            ')' => {
+                depth -= 1;
+                cur.push(c);
+            }
+            ' ' if depth == 0 => {
+                if !cur.is_empty() {
+                    out.push(std::mem::take(&mut cur));
+                }
+            }
+            _ => cur.push(c),
+        }
+    }
+    if !cur.is_empty() {
+        out.push(cur);
+    }
+    out
+}
+
+fn number(s: &str) -> FpgaApiResult<usize> {
+    s.parse()
+        .map_err(|_| FpgaApiError::from(format!("Expected a type-level number, got {:?}", s)))
+}
+
+fn scalar(ty: &str) -> FpgaApiResult<Scalar> {
+    let words = tokens(ty);
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    match words.as_slice() {
+        [inner] if inner.contains(' ') => scalar(inner),
+        ["Bool"] | ["Bit"] => Ok(Scalar::Bool),
+        ["Signed", n] => Scalar::Signed(number(n)?).check(),
+        ["Unsigned", n] | ["BitVector", n] => Scalar::Unsigned(number(n)?).check(),
+        ["SFixed", i, f] => Scalar::SFixed(number(i)?, number(f)?).check(),
+        ["UFixed", i, f] => Scalar::UFixed(number(i)?, number(f)?).check(),
+        _ => Err(FpgaApiError::from(format!(
+            "Unsupported Clash port type {:?}",
+            ty.trim()
+        ))),
+    }
+}
+
+/// Argument and result types of a signature such as
+/// `Vec 2 (SFixed 7 25) -> Vec 1 (SFixed 7 25)`.
+pub fn parse_signature(sig: &str) -> FpgaApiResult<(Vec<PortType>, PortType)> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    let bytes = sig.as_bytes();
+    for (i, &c) in bytes.iter().enumerate() {
+        match c {
+            b'(' => depth += 1,
+            b')' => depth -= 1,
+            b'-' if depth == 0 && bytes.get(i + 1) == Some(&b'>') => {
+                parts.push(&sig[start..i]);
+                start = i + 2;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&sig[start..]);
+    let mut types = parts
+        .iter()
+        .map(|p| PortType::parse(p))
+        .collect::<FpgaApiResult<Vec<_>>>()?;
+    let result = types.pop().expect("split always yields a part");
+    Ok((types, result))
+}
+
+/// The type signature of `topEntity` in Haskell source.
+pub fn find_signature(source: &str) -> FpgaApiResult<String> {
+    let mut lines = source
+        .lines()
+        .skip_while(|l| !l.starts_with("topEntity ::"));
+    let first = lines
+        .next()
+        .ok_or_else(|| FpgaApiError::from("No topEntity type signature found!"))?;
+    let mut sig = first["topEntity ::".len()..].to_string();
+    // Continuation lines are indented.
+    for line in lines.take_while(|l| l.starts_with(char::is_whitespace)) {
+        sig.push(' ');
+        sig.push_str(line.trim());
+    }
+    Ok(sig.split("--").next().unwrap_or("").trim().to_string())
+}
+
+/// A port of the generated register map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenPort {
+    pub name: String,
+    pub input: bool,
+    pub ty: PortType,
+    pub offset: usize,
+}
+impl GenPort {
+    fn size(&self) -> usize {
+        self.len() * self.elem_size()
+    }
+    fn len(&self) -> usize {
+        self.ty.len.unwrap_or(1)
+    }
+    fn elem_size(&self) -> usize {
+        match self.ty.elem {
+            Scalar::Bool => 1,
+            s => s.bits() / 8,
+        }
+    }
+}
+
+/// Pair the manifest's ports with the signature's types and lay them out.
+pub fn layout(manifest: &Manifest, signature: &str) -> FpgaApiResult<Vec<GenPort>> {
+    let (args, result) = parse_signature(signature)?;
+    let ports: Vec<&Port> = manifest
+        .top_component
+        .ports_flat
+        .iter()
+        .filter(|p| !p.is_clock)
+        .collect();
+    let inputs: Vec<_> = ports.iter().filter(|p| p.direction == "in").collect();
+    let outputs: Vec<_> = ports.iter().filter(|p| p.direction == "out").collect();
+    if inputs.len() != args.len() || outputs.len() != 1 {
+        return Err(FpgaApiError::from(format!(
+            "Manifest of {} has {} inputs and {} outputs, the signature {} arguments and 1 result",
+            manifest.top_component.name,
+            inputs.len(),
+            outputs.len(),
+            args.len()
+        )));
+    }
+    let mut offset = 0;
+    let mut out = Vec::new();
+    let pairs = inputs
+        .iter()
+        .zip(args)
+        .map(|(p, t)| (p, t, true))
+        .chain(std::iter::once((&outputs[0], result, false)));
+    for (port, ty, input) in pairs {
+        if port.width != ty.bits() {
+            return Err(FpgaApiError::from(format!(
+                "Port {:?} is {} bits wide ({}), but its Clash type has {} bits",
+                port.name,
+                port.width,
+                port.type_name,
+                ty.bits()
+            )));
+        }
+        let gen = GenPort {
+            name: port.name.clone(),
+            input,
+            ty,
+            offset,
+        };
+        offset += gen.size().div_ceil(WORD_SIZE) * WORD_SIZE;
+        out.push(gen);
+    }
+    Ok(out)
+}
+
+fn camel(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut cs = w.chars();
+            cs.next()
+                .map(|c| c.to_ascii_uppercase().to_string() + cs.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+fn upper_snake(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 && !out.ends_with('_') {
+            out.push('_');
+        }
+        out.push(if c.is_ascii_alphanumeric() {
+            c.to_ascii_uppercase()
+        } else {
+            '_'
+        });
+    }
+    out
+}
+
+/// Rust source of the register map of `manifest`'s top entity. `krate` is the
+/// path of this library in the generated code (`sbtb`, or `crate` inside it).
+pub fn generate(manifest: &Manifest, signature: &str, krate: &str) -> FpgaApiResult<String> {
+    let ports = layout(manifest, signature)?;
+    let entity = &manifest.top_component.name;
+    let mut s = String::new();
+    let w = &mut s;
+    writeln!(
+        w,
+        "//! Register map of Clash entity `{}`, generated from its manifest and",
+        entity
+    )?;
+    writeln!(w, "//! the signature `topEntity :: {}`.", signature.trim())?;
+    writeln!(w, "//! DO NOT MODIFY.")?;
+    writeln!(w)?;
+    writeln!(w, "#![allow(dead_code)]")?;
+    writeln!(w)?;
+    writeln!(
+        w,
+        "use {}::regmap::{{Access, Register, RegisterMap}};",
+        krate
+    )?;
+    writeln!(w, "use {}::resources::Resource;", krate)?;
+    writeln!(w, "use {}::traits::{{Data, ReadOnly, ReadWrite}};", krate)?;
+    writeln!(w, "use {}::{{FpgaApiError, FpgaApiResult}};", krate)?;
+    let mut types = Vec::new();
+    for port in &ports {
+        let konst = upper_snake(&port.name);
+        let elem = port.ty.elem.rust_type();
+        let ty = match port.ty.len {
+            Some(n) if n > 1 => {
+                let ty = camel(&port.name);
+                write_array_type(w, &ty, &elem, n)?;
+                ty
+            }
+            _ => elem,
+        };
+        let state = if port.input { "ReadWrite" } else { "ReadOnly" };
+        writeln!(w)?;
+        writeln!(w, "pub const {}_OFFSET: usize = {};", konst, port.offset)?;
+        writeln!(w, "/// Port `{}` of `{}`.", port.name, entity)?;
+        writeln!(
+            w,
+            "pub fn {}() -> Resource<{}, {}> {{",
+            port_fn(&port.name),
+            ty,
+            state
+        )?;
+        writeln!(w, "    Resource::new({:?}, {}_OFFSET)", port.name, konst)?;
+        writeln!(w, "}}")?;
+        types.push(ty);
+    }
+    writeln!(w)?;
+    writeln!(
+        w,
+        "pub const {}_REGISTER_MAP: RegisterMap = RegisterMap::new(",
+        upper_snake(entity)
+    )?;
+    writeln!(w, "    {:?},", entity)?;
+    writeln!(w, "    &[")?;
+    // Sizes come from the `Data` types, so the map cannot disagree with the
+    // resources.
+    for (port, ty) in ports.iter().zip(&types) {
+        let access = if port.input { "ReadWrite" } else { "ReadOnly" };
+        let vector = match port.ty.len {
+            Some(n) if n > 1 => format!(".vector({})", n),
+            _ => String::new(),
+        };
+        writeln!(
+            w,
+            "        Register::new({:?}, {}_OFFSET, <{} as Data>::SIZE, Access::{}){},",
+            port.name,
+            upper_snake(&port.name),
+            ty,
+            access,
+            vector
+        )?;
+    }
+    writeln!(w, "    ],")?;
+    writeln!(w, ");")?;
+    Ok(s)
+}
+
+/// Function name for a port, avoiding Rust keywords such as `in`.
+fn port_fn(name: &str) -> String {
+    format!("{}_register", upper_snake(name).to_ascii_lowercase())
+}
+
+fn write_array_type(w: &mut String, ty: &str, elem: &str, n: usize) -> std::fmt::Result {
+    writeln!(w)?;
+    writeln!(w, "#[derive(Debug, Clone, Copy, PartialEq)]")?;
+    writeln!(w, "pub struct {}(pub [{}; {}]);", ty, elem, n)?;
+    writeln!(w, "impl Data for {} {{", ty)?;
+    writeln!(
+        w,
+        "    const SIZE: usize = {} * <{} as Data>::SIZE;",
+        n, elem
+    )?;
+    for (dir, read) in [("le", true), ("be", true), ("le", false), ("be", false)] {
+        if read {
+            writeln!(
+                w,
+                "    fn from_{}_bytes(b: &[u8]) -> FpgaApiResult<Self> {{",
+                dir
+            )?;
+            writeln!(w, "        if b.len() != Self::SIZE {{")?;
+            writeln!(
+                w,
+                "            return Err(FpgaApiError::from(\"Wrong number of bytes!\"));"
+            )?;
+            writeln!(w, "        }}")?;
+            writeln!(w, "        let mut v = [<{}>::default(); {}];", elem, n)?;
+            writeln!(
+                w,
+                "        for (v, ch) in v.iter_mut().zip(b.chunks_exact(<{} as Data>::SIZE)) {{",
+                elem
+            )?;
+            writeln!(
+                w,
+                "            *v = <{} as Data>::from_{}_bytes(ch)?;",
+                elem, dir
+            )?;
+            writeln!(w, "        }}")?;
+            writeln!(w, "        Ok(Self(v))")?;
+        } else {
+            writeln!(
+                w,
+                "    fn write_{}_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {{",
+                dir
+            )?;
+            writeln!(w, "        if b.len() != Self::SIZE {{")?;
+            writeln!(
+                w,
+                "            return Err(FpgaApiError::from(\"Wrong number of bytes!\"));"
+            )?;
+            writeln!(w, "        }}")?;
+            writeln!(
+                w,
+                "        for (v, ch) in self.0.iter().zip(b.chunks_exact_mut(<{} as Data>::SIZE)) {{",
+                elem
+            )?;
+            writeln!(
+                w,
+                "            <{} as Data>::write_{}_bytes(*v, ch)?;",
+                elem, dir
+            )?;
+            writeln!(w, "        }}")?;
+            writeln!(w, "        Ok(())")?;
+        }
+        writeln!(w, "    }}")?;
+    }
+    writeln!(w, "}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn repo_file(path: &str) -> String {
+        std::fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join(path))
+            .unwrap()
+    }
+
+    fn run_network() -> (Manifest, String) {
+        let manifest = Manifest::from_json(&repo_file("quartus-project/clash-manifest.json"));
+        let signature = find_signature(&repo_file("ip/RunNetwork.hs")).unwrap();
+        (manifest.unwrap(), signature)
+    }
+
+    fn manifest(ports: &[(&str, &str, usize)]) -> Manifest {
+        Manifest {
+            top_component: Component {
+                name: "top".to_string(),
+                ports_flat: ports
+                    .iter()
+                    .map(|&(name, direction, width)| Port {
+                        name: name.to_string(),
+                        direction: direction.to_string(),
+                        width,
+                        is_clock: false,
+                        type_name: String::new(),
+                    })
+                    .collect(),
+            },
+        }
+    }
+
+    #[test]
+    fn run_network_signature_is_found() {
+        let (_, signature) = run_network();
+        assert_eq!(signature, "Vec 2 (SFixed 7 25) -> Vec 1 (SFixed 7 25)");
+    }
+
+    #[test]
+    fn checked_in_run_network_module_is_up_to_date() {
+        let (manifest, signature) = run_network();
+        assert_eq!(
+            generate(&manifest, &signature, "crate").unwrap(),
+            include_str!("run_network.rs")
+        );
+    }
+
+    #[test]
+    fn run_network_layout_matches_point_nn_register_map() {
+        let (manifest, signature) = run_network();
+        let ports = layout(&manifest, &signature).unwrap();
+        let map = crate::POINT_NN_REGISTER_MAP;
+        assert_eq!(ports.len(), map.registers.len());
+        for (port, reg) in ports.iter().zip(map.registers) {
+            assert_eq!(port.name, reg.name);
+            assert_eq!(port.offset, reg.offset);
+            assert_eq!(port.size(), reg.size);
+            assert_eq!(port.input, reg.access.writable());
+            assert_eq!(port.ty.len.filter(|&n| n > 1).unwrap_or(1), reg.elements);
+        }
+        map.check().unwrap();
+    }
+
+    #[test]
+    fn scalars_map_to_rust_types() {
+        let ty = |s| PortType::parse(s).unwrap().elem.rust_type();
+        assert_eq!(ty("Bit"), "bool");
+        assert_eq!(ty("Signed 16"), "i16");
+        assert_eq!(ty("BitVector 64"), "u64");
+        assert_eq!(ty("SFixed 1 7"), "fixed::types::I1F7");
+        assert_eq!(ty("UFixed 0 32"), "fixed::types::U0F32");
+        assert_eq!(ty("(SFixed 7 25)"), "fixed::types::I7F25");
+    }
+
+    #[test]
+    fn unsupported_types_are_rejected() {
+        for ty in &[
+            "Signed 12",
+            "SFixed 7 26",
+            "Vec 2 (Signed 16)",
+            "Vec n (Signed 32)",
+            "Maybe Bool",
+        ] {
+            assert!(PortType::parse(ty).is_err(), "{}", ty);
+        }
+    }
+
+    #[test]
+    fn ports_are_laid_out_word_by_word() {
+        let manifest = manifest(&[("a", "in", 1), ("b", "in", 64), ("c", "out", 16)]);
+        let ports = layout(&manifest, "Bool -> Signed 64 -> UFixed 8 8").unwrap();
+        let offsets: Vec<_> = ports.iter().map(|p| p.offset).collect();
+        assert_eq!(offsets, [0, 4, 12]);
+        assert!(ports[0].input && ports[1].input && !ports[2].input);
+    }
+
+    #[test]
+    fn manifest_and_signature_must_agree() {
+        let manifest = manifest(&[("a", "in", 32), ("b", "out", 32)]);
+        assert!(layout(&manifest, "Signed 32 -> Signed 32").is_ok());
+        assert!(layout(&manifest, "Signed 16 -> Signed 32").is_err());
+        assert!(layout(&manifest, "Signed 32 -> Signed 32 -> Signed 32").is_err());
+    }
+}
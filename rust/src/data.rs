@@ -1,79 +1,144 @@
 //! Define FPGA data types and primitives.
 
-use fixed::types::I7F25;
+use core::convert::TryInto;
+
+use fixed::types::extra::{LeEqU16, LeEqU32, LeEqU64, LeEqU8};
+use fixed::{FixedI16, FixedI32, FixedI64, FixedI8, FixedU16, FixedU32, FixedU64, FixedU8};
 
 use crate::traits::Data;
 use crate::{FpgaApiError, FpgaApiResult};
 
+impl Data for bool {
+    const SIZE: usize = 1;
+    fn from_le_bytes(b: &[u8]) -> FpgaApiResult<Self> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            match b[0] {
+                0 => Ok(false),
+                1 => Ok(true),
+                _ => Err(FpgaApiError::from("Invalid bool encoding!")),
+            }
+        }
+    }
+    fn from_be_bytes(b: &[u8]) -> FpgaApiResult<Self> {
+        <bool as Data>::from_le_bytes(b)
+    }
+    fn write_le_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b[0] = self as u8;
+            Ok(())
+        }
+    }
+    fn write_be_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        self.write_le_bytes(b)
+    }
+}
 impl Data for u8 {
+    const SIZE: usize = 1;
     fn from_le_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(b[0])
         }
     }
     fn from_be_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(b[0])
         }
     }
-    fn to_le_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_le_bytes())
+    fn write_le_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_le_bytes());
+            Ok(())
+        }
     }
-    fn to_be_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_be_bytes())
+    fn write_be_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_be_bytes());
+            Ok(())
+        }
     }
 }
 impl Data for u16 {
+    const SIZE: usize = 2;
     fn from_le_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(u16::from_le_bytes([b[0], b[1]]))
         }
     }
     fn from_be_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(u16::from_be_bytes([b[0], b[1]]))
         }
     }
-    fn to_le_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_le_bytes())
+    fn write_le_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_le_bytes());
+            Ok(())
+        }
     }
-    fn to_be_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_be_bytes())
+    fn write_be_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_be_bytes());
+            Ok(())
+        }
     }
 }
 impl Data for u32 {
+    const SIZE: usize = 4;
     fn from_le_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
         }
     }
     fn from_be_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
         }
     }
-    fn to_le_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_le_bytes())
+    fn write_le_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_le_bytes());
+            Ok(())
+        }
     }
-    fn to_be_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_be_bytes())
+    fn write_be_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_be_bytes());
+            Ok(())
+        }
     }
 }
 impl Data for u64 {
+    const SIZE: usize = 8;
     fn from_le_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(u64::from_le_bytes([
@@ -82,7 +147,7 @@ impl Data for u64 {
         }
     }
     fn from_be_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(u64::from_be_bytes([
@@ -90,82 +155,126 @@ impl Data for u64 {
             ]))
         }
     }
-    fn to_le_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_le_bytes())
+    fn write_le_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_le_bytes());
+            Ok(())
+        }
     }
-    fn to_be_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_be_bytes())
+    fn write_be_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_be_bytes());
+            Ok(())
+        }
     }
 }
 impl Data for i8 {
+    const SIZE: usize = 1;
     fn from_le_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(i8::from_le_bytes([b[0]]))
         }
     }
     fn from_be_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(i8::from_be_bytes([b[0]]))
         }
     }
-    fn to_le_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_le_bytes())
+    fn write_le_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_le_bytes());
+            Ok(())
+        }
     }
-    fn to_be_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_be_bytes())
+    fn write_be_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_be_bytes());
+            Ok(())
+        }
     }
 }
 impl Data for i16 {
+    const SIZE: usize = 2;
     fn from_le_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(i16::from_le_bytes([b[0], b[1]]))
         }
     }
     fn from_be_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(i16::from_be_bytes([b[0], b[1]]))
         }
     }
-    fn to_le_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_le_bytes())
+    fn write_le_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_le_bytes());
+            Ok(())
+        }
     }
-    fn to_be_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_be_bytes())
+    fn write_be_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_be_bytes());
+            Ok(())
+        }
     }
 }
 impl Data for i32 {
+    const SIZE: usize = 4;
     fn from_le_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
         }
     }
     fn from_be_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(i32::from_be_bytes([b[0], b[1], b[2], b[3]]))
         }
     }
-    fn to_le_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_le_bytes())
+    fn write_le_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_le_bytes());
+            Ok(())
+        }
     }
-    fn to_be_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_be_bytes())
+    fn write_be_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_be_bytes());
+            Ok(())
+        }
     }
 }
 impl Data for i64 {
+    const SIZE: usize = 8;
     fn from_le_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(i64::from_le_bytes([
@@ -174,7 +283,7 @@ impl Data for i64 {
         }
     }
     fn from_be_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(i64::from_be_bytes([
@@ -182,38 +291,60 @@ impl Data for i64 {
             ]))
         }
     }
-    fn to_le_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_le_bytes())
+    fn write_le_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_le_bytes());
+            Ok(())
+        }
     }
-    fn to_be_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_be_bytes())
+    fn write_be_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_be_bytes());
+            Ok(())
+        }
     }
 }
 impl Data for f32 {
+    const SIZE: usize = 4;
     fn from_le_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
         }
     }
     fn from_be_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(f32::from_be_bytes([b[0], b[1], b[2], b[3]]))
         }
     }
-    fn to_le_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_le_bytes())
+    fn write_le_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_le_bytes());
+            Ok(())
+        }
     }
-    fn to_be_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_be_bytes())
+    fn write_be_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_be_bytes());
+            Ok(())
+        }
     }
 }
 impl Data for f64 {
+    const SIZE: usize = 8;
     fn from_le_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(f64::from_le_bytes([
@@ -222,7 +353,7 @@ impl Data for f64 {
         }
     }
     fn from_be_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
             Ok(f64::from_be_bytes([
@@ -230,88 +361,265 @@ impl Data for f64 {
             ]))
         }
     }
-    fn to_le_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_le_bytes())
+    fn write_le_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_le_bytes());
+            Ok(())
+        }
     }
-    fn to_be_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_be_bytes())
+    fn write_be_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            Err(FpgaApiError::from("Wrong number of bytes!"))
+        } else {
+            b.copy_from_slice(&self.to_be_bytes());
+            Ok(())
+        }
     }
 }
-impl Data for I7F25 {
+/// `Data` for every fixed-point split of a primitive width, so that any
+/// Clash `SFixed i f` or `UFixed i f` of 8 to 64 bits has a Rust counterpart.
+macro_rules! fixed_data {
+    ($($Fixed:ident: $LeEq:ident, $n:expr;)*) => {$(
+        impl<Frac: $LeEq> Data for $Fixed<Frac> {
+            const SIZE: usize = $n;
+            fn from_le_bytes(b: &[u8]) -> FpgaApiResult<Self> {
+                b.try_into()
+                    .map(Self::from_le_bytes)
+                    .map_err(|_| FpgaApiError::from("Wrong number of bytes!"))
+            }
+            fn from_be_bytes(b: &[u8]) -> FpgaApiResult<Self> {
+                b.try_into()
+                    .map(Self::from_be_bytes)
+                    .map_err(|_| FpgaApiError::from("Wrong number of bytes!"))
+            }
+            fn write_le_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+                if b.len() != Self::SIZE {
+                    Err(FpgaApiError::from("Wrong number of bytes!"))
+                } else {
+                    b.copy_from_slice(&self.to_le_bytes());
+                    Ok(())
+                }
+            }
+            fn write_be_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+                if b.len() != Self::SIZE {
+                    Err(FpgaApiError::from("Wrong number of bytes!"))
+                } else {
+                    b.copy_from_slice(&self.to_be_bytes());
+                    Ok(())
+                }
+            }
+        }
+    )*};
+}
+fixed_data! {
+    FixedI8: LeEqU8, 1;
+    FixedI16: LeEqU16, 2;
+    FixedI32: LeEqU32, 4;
+    FixedI64: LeEqU64, 8;
+    FixedU8: LeEqU8, 1;
+    FixedU16: LeEqU16, 2;
+    FixedU32: LeEqU32, 4;
+    FixedU64: LeEqU64, 8;
+}
+/// A pair is laid out as `A` followed by `B`.
+impl<A: Data, B: Data> Data for (A, B) {
+    const SIZE: usize = A::SIZE + B::SIZE;
     fn from_le_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
-            Ok(I7F25::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            let (a, b) = b.split_at(A::SIZE);
+            Ok((A::from_le_bytes(a)?, B::from_le_bytes(b)?))
         }
     }
     fn from_be_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        if b.len() != std::mem::size_of::<Self>() {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
-            Ok(I7F25::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            let (a, b) = b.split_at(A::SIZE);
+            Ok((A::from_be_bytes(a)?, B::from_be_bytes(b)?))
         }
     }
-    fn to_le_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_le_bytes())
-    }
-    fn to_be_bytes(self) -> Vec<u8> {
-        Vec::from(self.to_be_bytes())
-    }
-}
-impl Data for (I7F25, I7F25) {
-    fn from_le_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        let zero = I7F25::from_num(0);
-        if b.len() != std::mem::size_of::<Self>() {
+    fn write_le_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
-            let mut byte_arr = [0; 8];
-            for (&b, v) in b.iter().zip(byte_arr.iter_mut()) {
-                *v = b;
-            }
-            let mut data_arr = [zero; 2];
-            for (b, v) in byte_arr
-                .chunks_exact(4)
-                .map(|ch| I7F25::from_le_bytes([ch[0], ch[1], ch[2], ch[3]]))
-                .zip(data_arr.iter_mut())
-            {
-                *v = b;
-            }
-            Ok((data_arr[0], data_arr[1]))
+            let (a, b) = b.split_at_mut(A::SIZE);
+            self.0.write_le_bytes(a)?;
+            self.1.write_le_bytes(b)
         }
     }
-    fn from_be_bytes(b: &[u8]) -> FpgaApiResult<Self> {
-        let zero = I7F25::from_num(0);
-        if b.len() != std::mem::size_of::<Self>() {
+    fn write_be_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
             Err(FpgaApiError::from("Wrong number of bytes!"))
         } else {
-            let mut byte_arr = [0; 8];
-            for (&b, v) in b.iter().zip(byte_arr.iter_mut()) {
-                *v = b;
-            }
-            let mut data_arr = [zero; 2];
-            for (b, v) in byte_arr
-                .chunks_exact(4)
-                .map(|ch| I7F25::from_be_bytes([ch[0], ch[1], ch[2], ch[3]]))
-                .zip(data_arr.iter_mut())
-            {
-                *v = b;
-            }
-            Ok((data_arr[0], data_arr[1]))
-        }
-    }
-    fn to_le_bytes(self) -> Vec<u8> {
-        let (v1, v2) = self;
-        let mut vec = Vec::with_capacity(8);
-        vec.extend_from_slice(&v1.to_le_bytes());
-        vec.extend_from_slice(&v2.to_le_bytes());
-        vec
-    }
-    fn to_be_bytes(self) -> Vec<u8> {
-        let (v1, v2) = self;
-        let mut vec = Vec::with_capacity(8);
-        vec.extend_from_slice(&v1.to_be_bytes());
-        vec.extend_from_slice(&v2.to_be_bytes());
-        vec
+            let (a, b) = b.split_at_mut(A::SIZE);
+            self.0.write_be_bytes(a)?;
+            self.1.write_be_bytes(b)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixed::types::I7F25;
+
+    /// Check that `val` encodes to `le`/`be`, decodes back, and that both
+    /// directions reject slices one byte too short or too long.
+    fn round_trip<D: Data + Copy + PartialEq + core::fmt::Debug>(val: D, le: &[u8], be: &[u8]) {
+        let mut buf = [0u8; 9];
+        val.write_le_bytes(&mut buf[..D::SIZE]).unwrap();
+        assert_eq!(&buf[..D::SIZE], le);
+        assert_eq!(D::from_le_bytes(le).unwrap(), val);
+        val.write_be_bytes(&mut buf[..D::SIZE]).unwrap();
+        assert_eq!(&buf[..D::SIZE], be);
+        assert_eq!(D::from_be_bytes(be).unwrap(), val);
+
+        for len in [D::SIZE - 1, D::SIZE + 1] {
+            assert!(val.write_le_bytes(&mut buf[..len]).is_err());
+            assert!(val.write_be_bytes(&mut buf[..len]).is_err());
+            assert!(D::from_le_bytes(&buf[..len]).is_err());
+            assert!(D::from_be_bytes(&buf[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn bool_round_trip() {
+        round_trip(false, &[0], &[0]);
+        round_trip(true, &[1], &[1]);
+        assert!(<bool as Data>::from_le_bytes(&[2]).is_err());
+        assert!(<bool as Data>::from_be_bytes(&[0xFF]).is_err());
+    }
+
+    #[test]
+    fn u8_round_trip() {
+        round_trip(0u8, &[0], &[0]);
+        round_trip(0xA5u8, &[0xA5], &[0xA5]);
+    }
+
+    #[test]
+    fn u16_round_trip() {
+        round_trip(0x0102u16, &[2, 1], &[1, 2]);
+        round_trip(u16::MAX, &[0xFF; 2], &[0xFF; 2]);
+    }
+
+    #[test]
+    fn u32_round_trip() {
+        round_trip(0x0102_0304u32, &[4, 3, 2, 1], &[1, 2, 3, 4]);
+        round_trip(u32::MAX, &[0xFF; 4], &[0xFF; 4]);
+    }
+
+    #[test]
+    fn u64_round_trip() {
+        round_trip(
+            0x0102_0304_0506_0708u64,
+            &[8, 7, 6, 5, 4, 3, 2, 1],
+            &[1, 2, 3, 4, 5, 6, 7, 8],
+        );
+        round_trip(u64::MAX, &[0xFF; 8], &[0xFF; 8]);
+    }
+
+    #[test]
+    fn i8_round_trip() {
+        round_trip(-1i8, &[0xFF], &[0xFF]);
+        round_trip(i8::MIN, &[0x80], &[0x80]);
+    }
+
+    #[test]
+    fn i16_round_trip() {
+        round_trip(-2i16, &[0xFE, 0xFF], &[0xFF, 0xFE]);
+        round_trip(i16::MIN, &[0, 0x80], &[0x80, 0]);
+    }
+
+    #[test]
+    fn i32_round_trip() {
+        round_trip(-2i32, &[0xFE, 0xFF, 0xFF, 0xFF], &[0xFF, 0xFF, 0xFF, 0xFE]);
+        round_trip(0x0102_0304i32, &[4, 3, 2, 1], &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn i64_round_trip() {
+        round_trip(
+            -2i64,
+            &[0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE],
+        );
+        round_trip(
+            0x0102_0304_0506_0708i64,
+            &[8, 7, 6, 5, 4, 3, 2, 1],
+            &[1, 2, 3, 4, 5, 6, 7, 8],
+        );
+    }
+
+    #[test]
+    fn f32_round_trip() {
+        round_trip(1.0f32, &[0, 0, 0x80, 0x3F], &[0x3F, 0x80, 0, 0]);
+        round_trip(-2.5f32, &[0, 0, 0x20, 0xC0], &[0xC0, 0x20, 0, 0]);
+    }
+
+    #[test]
+    fn f64_round_trip() {
+        round_trip(
+            1.0f64,
+            &[0, 0, 0, 0, 0, 0, 0xF0, 0x3F],
+            &[0x3F, 0xF0, 0, 0, 0, 0, 0, 0],
+        );
+        round_trip(
+            -2.5f64,
+            &[0, 0, 0, 0, 0, 0, 0x04, 0xC0],
+            &[0xC0, 0x04, 0, 0, 0, 0, 0, 0],
+        );
+    }
+
+    #[test]
+    fn i7f25_round_trip() {
+        // 1.0 is 1 << 25.
+        round_trip(I7F25::from_num(1), &[0, 0, 0, 2], &[2, 0, 0, 0]);
+        round_trip(I7F25::from_num(-1), &[0, 0, 0, 0xFE], &[0xFE, 0, 0, 0]);
+        round_trip(I7F25::from_bits(1), &[1, 0, 0, 0], &[0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn other_fixed_splits_round_trip() {
+        use fixed::types::{I0F32, I1F7, I8F8, U0F8, U16F16, U32F32};
+        round_trip(I1F7::from_num(-1), &[0x80], &[0x80]);
+        round_trip(U0F8::from_num(0.5), &[0x80], &[0x80]);
+        round_trip(I8F8::from_num(1), &[0, 1], &[1, 0]);
+        round_trip(
+            I0F32::from_bits(-2),
+            &[0xFE, 0xFF, 0xFF, 0xFF],
+            &[0xFF, 0xFF, 0xFF, 0xFE],
+        );
+        round_trip(U16F16::from_num(2), &[0, 0, 2, 0], &[0, 2, 0, 0]);
+        round_trip(
+            U32F32::from_num(1),
+            &[0, 0, 0, 0, 1, 0, 0, 0],
+            &[0, 0, 0, 1, 0, 0, 0, 0],
+        );
+    }
+
+    #[test]
+    fn i7f25_pair_round_trip() {
+        // Each element is encoded on its own, element 0 first.
+        round_trip(
+            (I7F25::from_num(1), I7F25::from_bits(1)),
+            &[0, 0, 0, 2, 1, 0, 0, 0],
+            &[2, 0, 0, 0, 0, 0, 0, 1],
+        );
+        round_trip(
+            (I7F25::from_num(-1), I7F25::from_num(0)),
+            &[0, 0, 0, 0xFE, 0, 0, 0, 0],
+            &[0xFE, 0, 0, 0, 0, 0, 0, 0],
+        );
+    }
+
+    #[test]
+    fn mixed_pair_round_trip() {
+        round_trip((0x0102u16, true), &[2, 1, 1], &[1, 2, 1]);
+        round_trip((0x01i8, (0x0203u16, 0x04u8)), &[1, 3, 2, 4], &[1, 2, 3, 4]);
+        assert!(<(u16, bool)>::from_le_bytes(&[0, 0, 2]).is_err());
     }
 }
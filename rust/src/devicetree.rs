@@ -0,0 +1,569 @@
+//! Locating FPGA cores through the device tree.
+//!
+//! The tree is read either from the kernel's `/proc/device-tree` directory
+//! (one directory per node, one file per property) or from a flattened
+//! device tree blob (`.dtb`). Nodes are found by `compatible` string; their
+//! `reg` entries are translated through the `ranges` of every parent bus
+//! (e.g. the HPS-to-FPGA bridges) into CPU physical addresses, ready to be
+//! turned into a `Region`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::region::Region;
+use crate::{FpgaApiError, FpgaApiResult};
+
+const PROC_DEVICE_TREE: &str = "/proc/device-tree";
+
+const FDT_MAGIC: u32 = 0xD00D_FEED;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// A device tree node with its raw property values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// Node name including the unit address, e.g. `runNetworkAVS@20000`.
+    pub name: String,
+    pub properties: BTreeMap<String, Vec<u8>>,
+    pub children: Vec<Node>,
+}
+impl Node {
+    /// Raw value of property `name`.
+    pub fn property(&self, name: &str) -> Option<&[u8]> {
+        self.properties.get(name).map(Vec::as_slice)
+    }
+    /// Property `name` as a list of big-endian 32-bit cells.
+    pub fn cells(&self, name: &str) -> Option<Vec<u32>> {
+        self.property(name).map(|p| {
+            p.chunks_exact(4)
+                .map(|c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]))
+                .collect()
+        })
+    }
+    /// Property `name` as a single cell.
+    pub fn u32(&self, name: &str) -> Option<u32> {
+        self.cells(name).and_then(|c| c.first().copied())
+    }
+    /// Strings of a string-list property such as `compatible`.
+    pub fn strings(&self, name: &str) -> Vec<&str> {
+        self.property(name)
+            .map(|p| {
+                p.split(|&b| b == 0)
+                    .filter(|s| !s.is_empty())
+                    .filter_map(|s| std::str::from_utf8(s).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+    /// Whether `compatible` lists `compat`.
+    pub fn is_compatible(&self, compat: &str) -> bool {
+        self.strings("compatible").contains(&compat)
+    }
+    fn address_cells(&self) -> usize {
+        self.u32("#address-cells").unwrap_or(2) as usize
+    }
+    fn size_cells(&self) -> usize {
+        self.u32("#size-cells").unwrap_or(1) as usize
+    }
+}
+
+/// A core found in the device tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    /// Full node path, e.g. `/soc/bridge@c0000000/runNetworkAVS@20000`.
+    pub path: String,
+    /// `(address, size)` of every `reg` entry, as CPU physical addresses.
+    pub reg: Vec<(u64, u64)>,
+    /// Interrupt specifiers, grouped by the interrupt parent's
+    /// `#interrupt-cells`.
+    pub interrupts: Vec<Vec<u32>>,
+}
+impl Device {
+    /// Region for `reg` entry `index`, to open a session on.
+    pub fn region(&self, name: &'static str, index: usize) -> FpgaApiResult<Region> {
+        let (base, size) = self
+            .reg
+            .get(index)
+            .ok_or_else(|| format!("Device {} has no reg entry {}", self.path, index))?;
+        Ok(Region::new(name, *base, *size as usize))
+    }
+}
+
+/// A parsed device tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceTree {
+    pub root: Node,
+}
+impl DeviceTree {
+    /// Read the live device tree from `/proc/device-tree`.
+    pub fn load() -> FpgaApiResult<Self> {
+        Self::from_dir(Path::new(PROC_DEVICE_TREE))
+    }
+    /// Read a device tree in the `/proc/device-tree` directory layout.
+    pub fn from_dir(path: &Path) -> FpgaApiResult<Self> {
+        Ok(Self {
+            root: read_dir_node(path, String::new())?,
+        })
+    }
+    /// Read a flattened device tree blob from a file.
+    pub fn from_dtb_file(path: &Path) -> FpgaApiResult<Self> {
+        Self::from_dtb(&fs::read(path)?)
+    }
+    /// Parse a flattened device tree blob.
+    pub fn from_dtb(blob: &[u8]) -> FpgaApiResult<Self> {
+        let header = |i: usize| be_u32(blob, 4 * i);
+        if header(0)? != FDT_MAGIC {
+            return Err(FpgaApiError::from("Not a flattened device tree blob!"));
+        }
+        if header(1)? as usize > blob.len() {
+            return Err(truncated());
+        }
+        let strings = header(3)? as usize;
+        let mut parser = FdtParser {
+            blob,
+            strings,
+            pos: header(2)? as usize,
+        };
+        loop {
+            match parser.token()? {
+                FDT_NOP => continue,
+                FDT_BEGIN_NODE => break,
+                _ => return Err(FpgaApiError::from("Device tree blob has no root node!")),
+            }
+        }
+        let root = parser.node()?;
+        Ok(Self { root })
+    }
+    /// All nodes whose `compatible` lists `compat`, in tree order.
+    pub fn find_compatible(&self, compat: &str) -> FpgaApiResult<Vec<Device>> {
+        let mut phandles = BTreeMap::new();
+        index_phandles(&self.root, &mut phandles);
+        let mut found = Vec::new();
+        let mut path = Vec::new();
+        walk(&self.root, &mut path, compat, &phandles, &mut found)?;
+        Ok(found)
+    }
+    /// The single node compatible with `compat`.
+    pub fn find_one(&self, compat: &str) -> FpgaApiResult<Device> {
+        let mut found = self.find_compatible(compat)?;
+        match found.len() {
+            1 => Ok(found.remove(0)),
+            0 => Err(FpgaApiError::from(format!(
+                "No device tree node is compatible with {:?}",
+                compat
+            ))),
+            n => Err(FpgaApiError::from(format!(
+                "{} device tree nodes are compatible with {:?}",
+                n, compat
+            ))),
+        }
+    }
+}
+
+/// Collect the nodes under `node` compatible with `compat`; `path` holds its
+/// ancestors.
+fn walk<'a>(
+    node: &'a Node,
+    path: &mut Vec<&'a Node>,
+    compat: &str,
+    phandles: &BTreeMap<u32, &'a Node>,
+    found: &mut Vec<Device>,
+) -> FpgaApiResult<()> {
+    if node.is_compatible(compat) {
+        found.push(Device {
+            path: node_path(path, node),
+            reg: translated_reg(path, node)?,
+            interrupts: interrupts(path, node, phandles),
+        });
+    }
+    path.push(node);
+    for child in &node.children {
+        walk(child, path, compat, phandles, found)?;
+    }
+    path.pop();
+    Ok(())
+}
+
+fn read_dir_node(path: &Path, name: String) -> FpgaApiResult<Node> {
+    let mut entries = fs::read_dir(path)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    let mut node = Node {
+        name,
+        properties: BTreeMap::new(),
+        children: Vec::new(),
+    };
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.file_type()?.is_dir() {
+            node.children.push(read_dir_node(&entry.path(), name)?);
+        } else {
+            node.properties.insert(name, fs::read(entry.path())?);
+        }
+    }
+    Ok(node)
+}
+
+fn be_u32(blob: &[u8], pos: usize) -> FpgaApiResult<u32> {
+    pos.checked_add(4)
+        .and_then(|end| blob.get(pos..end))
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(truncated)
+}
+
+struct FdtParser<'a> {
+    blob: &'a [u8],
+    strings: usize,
+    pos: usize,
+}
+impl FdtParser<'_> {
+    fn token(&mut self) -> FpgaApiResult<u32> {
+        let token = be_u32(self.blob, self.pos)?;
+        self.pos += 4;
+        Ok(token)
+    }
+    /// NUL-terminated string at `pos`.
+    fn c_str(&self, pos: usize) -> FpgaApiResult<String> {
+        let rest = self.blob.get(pos..).ok_or_else(truncated)?;
+        let len = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| FpgaApiError::from("Unterminated device tree string!"))?;
+        Ok(String::from_utf8_lossy(&rest[..len]).into_owned())
+    }
+    /// Body of a node whose `FDT_BEGIN_NODE` token was just read.
+    fn node(&mut self) -> FpgaApiResult<Node> {
+        let name = self.c_str(self.pos)?;
+        self.pos = self.skip(name.len() + 1)?;
+        let mut node = Node {
+            name,
+            properties: BTreeMap::new(),
+            children: Vec::new(),
+        };
+        loop {
+            match self.token()? {
+                FDT_PROP => {
+                    let len = self.token()? as usize;
+                    let name_offset = self.token()? as usize;
+                    let name_pos = self.strings.checked_add(name_offset);
+                    let name = self.c_str(name_pos.ok_or_else(truncated)?)?;
+                    let end = self.skip(len)?;
+                    let value = self
+                        .blob
+                        .get(self.pos..self.pos + len)
+                        .ok_or_else(truncated)?;
+                    node.properties.insert(name, value.to_vec());
+                    self.pos = end;
+                }
+                FDT_BEGIN_NODE => node.children.push(self.node()?),
+                FDT_END_NODE => return Ok(node),
+                FDT_NOP => {}
+                FDT_END => return Err(FpgaApiError::from("Device tree node is not closed!")),
+                _ => return Err(FpgaApiError::from("Invalid device tree token!")),
+            }
+        }
+    }
+    /// Position after `len` bytes from the current one, padded to the next
+    /// token. Offsets come from the blob, so guard against overflow (`usize`
+    /// is 32 bits on the board).
+    fn skip(&self, len: usize) -> FpgaApiResult<usize> {
+        self.pos
+            .checked_add(len)
+            .and_then(|end| end.checked_add(3))
+            .map(|end| end & !3)
+            .ok_or_else(truncated)
+    }
+}
+
+fn truncated() -> FpgaApiError {
+    FpgaApiError::from("Device tree blob is truncated!")
+}
+
+fn node_path(parents: &[&Node], node: &Node) -> String {
+    if parents.is_empty() {
+        return "/".to_string();
+    }
+    parents[1..]
+        .iter()
+        .chain(std::iter::once(&node))
+        .fold(String::new(), |p, n| p + "/" + &n.name)
+}
+
+/// Combine big-endian cells into one number.
+fn cells_value(cells: &[u32]) -> u128 {
+    cells.iter().fold(0, |v, &c| v << 32 | c as u128)
+}
+
+/// `reg` of `node` translated through the `ranges` of every ancestor bus.
+fn translated_reg(parents: &[&Node], node: &Node) -> FpgaApiResult<Vec<(u64, u64)>> {
+    let reg = match node.cells("reg") {
+        Some(reg) => reg,
+        None => return Ok(Vec::new()),
+    };
+    let bus = match parents.last() {
+        Some(bus) => bus,
+        None => return Ok(Vec::new()),
+    };
+    let (ac, sc) = (bus.address_cells(), bus.size_cells());
+    let entry_len = match ac.checked_add(sc) {
+        Some(len) if len != 0 && reg.len() % len == 0 => len,
+        _ => {
+            return Err(FpgaApiError::from(format!(
+                "Malformed reg property of {}",
+                node_path(parents, node)
+            )))
+        }
+    };
+    reg.chunks(entry_len)
+        .map(|entry| {
+            let mut addr = cells_value(&entry[..ac]);
+            // Walk up from the node's bus to the root.
+            for i in (1..parents.len()).rev() {
+                addr = translate(parents[i], parents[i - 1], addr).ok_or_else(|| {
+                    format!(
+                        "Address {:#x} of {} is not mapped by {}",
+                        addr,
+                        node_path(parents, node),
+                        parents[i].name
+                    )
+                })?;
+            }
+            Ok((addr as u64, cells_value(&entry[ac..]) as u64))
+        })
+        .collect()
+}
+
+/// Translate a child address of `bus` into the address space of `parent`.
+/// A missing `ranges` means the bus is not mapped; an empty one means the
+/// identity mapping. Ranges with no (or impossibly many) cells map nothing.
+fn translate(bus: &Node, parent: &Node, addr: u128) -> Option<u128> {
+    let ranges = bus.cells("ranges")?;
+    if ranges.is_empty() {
+        return Some(addr);
+    }
+    let (cac, csc, pac) = (
+        bus.address_cells(),
+        bus.size_cells(),
+        parent.address_cells(),
+    );
+    let entry_len = cac.checked_add(pac)?.checked_add(csc)?;
+    if entry_len == 0 {
+        return None;
+    }
+    ranges.chunks_exact(entry_len).find_map(|r| {
+        let child = cells_value(&r[..cac]);
+        let parent = cells_value(&r[cac..cac + pac]);
+        let size = cells_value(&r[cac + pac..]);
+        if addr >= child && addr - child < size {
+            Some(parent + (addr - child))
+        } else {
+            None
+        }
+    })
+}
+
+fn index_phandles<'a>(node: &'a Node, phandles: &mut BTreeMap<u32, &'a Node>) {
+    if let Some(p) = node.u32("phandle").or_else(|| node.u32("linux,phandle")) {
+        phandles.insert(p, node);
+    }
+    for child in &node.children {
+        index_phandles(child, phandles);
+    }
+}
+
+/// `interrupts` of `node`, split by the interrupt parent's `#interrupt-cells`
+/// (the nearest `interrupt-parent`, on the node or an ancestor).
+fn interrupts(parents: &[&Node], node: &Node, phandles: &BTreeMap<u32, &Node>) -> Vec<Vec<u32>> {
+    let cells = match node.cells("interrupts") {
+        Some(cells) if !cells.is_empty() => cells,
+        _ => return Vec::new(),
+    };
+    let n = std::iter::once(node)
+        .chain(parents.iter().rev().copied())
+        .find_map(|n| n.u32("interrupt-parent"))
+        .and_then(|p| phandles.get(&p))
+        .and_then(|controller| controller.u32("#interrupt-cells"))
+        .filter(|&n| n > 0)
+        .unwrap_or(1) as usize;
+    cells.chunks(n).map(<[u32]>::to_vec).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const NETWORK: &str = "sbtb,runNetworkAVS-1.0";
+
+    fn testdata(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata")
+            .join(name)
+    }
+
+    /// The soc_system tree, read from the blob and from the directory.
+    fn trees() -> Vec<DeviceTree> {
+        vec![
+            DeviceTree::from_dtb_file(&testdata("soc_system.dtb")).unwrap(),
+            DeviceTree::from_dir(&testdata("soc_system")).unwrap(),
+        ]
+    }
+
+    fn header(blob: &mut [u8], i: usize, value: u32) {
+        blob[4 * i..4 * i + 4].copy_from_slice(&value.to_be_bytes());
+    }
+
+    fn node(name: &str, props: &[(&str, &[u32])], children: Vec<Node>) -> Node {
+        Node {
+            name: name.to_string(),
+            properties: props
+                .iter()
+                .map(|(k, v)| {
+                    (
+                        k.to_string(),
+                        v.iter().flat_map(|c| c.to_be_bytes()).collect(),
+                    )
+                })
+                .collect(),
+            children,
+        }
+    }
+
+    #[test]
+    fn network_is_found_behind_the_bridge() {
+        for tree in trees() {
+            let net = tree.find_one(NETWORK).unwrap();
+            assert_eq!(net.path, "/soc/bridge@c0000000/runNetworkAVS@20000");
+            assert_eq!(net.reg, vec![(0xC002_0000, 0x40)]);
+            let region = net.region("point_nn", 0).unwrap();
+            assert_eq!(region.base, crate::POINT_NN_REGION.base);
+            assert!(net.region("point_nn", 1).is_err());
+        }
+    }
+
+    #[test]
+    fn lightweight_bridge_range_is_translated() {
+        for tree in trees() {
+            let sysid = tree.find_one("altr,sysid-1.0").unwrap();
+            assert_eq!(sysid.reg, vec![(0xFF21_0030, 8)]);
+            assert_eq!(sysid.reg[0].0, crate::SYSTEM_ID_REGION.base);
+        }
+    }
+
+    #[test]
+    fn interrupts_are_grouped_by_the_controller_cells() {
+        for tree in trees() {
+            let net = tree.find_one(NETWORK).unwrap();
+            assert_eq!(net.interrupts, vec![vec![0, 40, 4], vec![0, 41, 4]]);
+            assert!(tree
+                .find_one("altr,sysid-1.0")
+                .unwrap()
+                .interrupts
+                .is_empty());
+        }
+    }
+
+    #[test]
+    fn find_compatible_returns_nodes_in_tree_order() {
+        for tree in trees() {
+            let buses = tree.find_compatible("simple-bus").unwrap();
+            let paths: Vec<_> = buses.iter().map(|d| d.path.as_str()).collect();
+            assert_eq!(paths, ["/soc", "/soc/bridge@c0000000"]);
+            // The bridge's own reg is in the soc bus, which maps 1:1.
+            assert_eq!(
+                buses[1].reg,
+                vec![(0xC000_0000, 0x2000_0000), (0xFF20_0000, 0x20_0000)]
+            );
+            assert!(tree.find_compatible("altr,uart-1.0").unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn find_one_needs_exactly_one_node() {
+        for tree in trees() {
+            assert!(tree.find_one("altr,uart-1.0").is_err());
+            assert!(tree.find_one("simple-bus").is_err());
+        }
+    }
+
+    #[test]
+    fn node_properties() {
+        let tree = &trees()[0];
+        assert_eq!(
+            tree.root.strings("compatible"),
+            [
+                "terasic,de0-nano-soc",
+                "altr,socfpga-cyclone5",
+                "altr,socfpga"
+            ]
+        );
+        assert_eq!(tree.root.u32("#address-cells"), Some(1));
+        assert_eq!(tree.root.property("missing"), None);
+    }
+
+    #[test]
+    fn bad_blobs_are_rejected() {
+        let blob = fs::read(testdata("soc_system.dtb")).unwrap();
+        assert!(DeviceTree::from_dtb(&blob[..blob.len() - 1]).is_err());
+        assert!(DeviceTree::from_dtb(&blob[..8]).is_err());
+
+        let mut bad_magic = blob.clone();
+        header(&mut bad_magic, 0, 0xFEED_D00D);
+        assert!(DeviceTree::from_dtb(&bad_magic).is_err());
+
+        // Offsets pointing far past the end must fail, not overflow.
+        for i in [2, 3] {
+            let mut bad = blob.clone();
+            header(&mut bad, i, u32::MAX);
+            assert!(DeviceTree::from_dtb(&bad).is_err());
+        }
+        // The first property of the root node: its length, then its name.
+        let prop = 0x38 + 4 + 4 + 4;
+        for (pos, value) in [(prop, u32::MAX), (prop + 4, u32::MAX)] {
+            let mut bad = blob.clone();
+            bad[pos..pos + 4].copy_from_slice(&value.to_be_bytes());
+            assert!(DeviceTree::from_dtb(&bad).is_err());
+        }
+    }
+
+    #[test]
+    fn unmapped_and_cellless_buses_are_errors() {
+        let mut dev = node("dev@0", &[("reg", &[0, 4])], vec![]);
+        dev.properties
+            .insert("compatible".to_string(), b"test,dev\0".to_vec());
+        let bus = |ranges: Option<&[u32]>| {
+            let mut props: Vec<(&str, &[u32])> =
+                vec![("#address-cells", &[1]), ("#size-cells", &[1])];
+            props.extend(ranges.map(|r| ("ranges", r)));
+            node("bus", &props, vec![dev.clone()])
+        };
+
+        // A bus without `ranges` is not visible to the CPU.
+        let unmapped = DeviceTree {
+            root: node("", &[], vec![bus(None)]),
+        };
+        assert!(unmapped.find_compatible("test,dev").is_err());
+
+        // A bridge with no address or size cells, under a root with none
+        // either, has zero-length `ranges` entries.
+        let cellless = DeviceTree {
+            root: node(
+                "",
+                &[("#address-cells", &[0]), ("#size-cells", &[0])],
+                vec![node(
+                    "bridge",
+                    &[
+                        ("#address-cells", &[0]),
+                        ("#size-cells", &[0]),
+                        ("ranges", &[0]),
+                    ],
+                    vec![bus(Some(&[]))],
+                )],
+            ),
+        };
+        assert!(cellless.find_compatible("test,dev").is_err());
+    }
+}
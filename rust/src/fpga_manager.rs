@@ -0,0 +1,585 @@
+//! Programming the FPGA through the Linux FPGA Manager framework.
+//!
+//! Images (`.rbf` files from `quartus-project/conversion_to_rbf.tcl`) are
+//! loaded from the firmware directory, either by applying a device tree
+//! overlay through configfs (`overlays/<name>/path`, then `status`) or, on
+//! kernels that expose it, through the manager's `firmware` attribute.
+//! Progress is followed through the manager's `state` attribute. Right after
+//! a load is triggered that attribute can still read `operating` from the
+//! previous image, so it only counts once the state has left `operating`
+//! (or the overlay has been applied). A successful load returns a `Loaded` token, which is required to open
+//! sessions on the fabric.
+//!
+//! All roots are configurable so a fake sysfs/configfs tree can stand in for
+//! the kernel's.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::region::RegionCell;
+use crate::FpgaApiResult;
+
+const FPGA_MANAGER_ROOT: &str = "/sys/class/fpga_manager/fpga0";
+const OVERLAYS_ROOT: &str = "/sys/kernel/config/device-tree/overlays";
+const FIRMWARE_ROOT: &str = "/lib/firmware";
+const POLL_PERIOD: Duration = Duration::from_millis(10);
+
+/// State of the FPGA, from the manager's `state` attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FpgaState {
+    PowerOff,
+    PowerUp,
+    PowerDown,
+    PowerError,
+    FirmwareRequest,
+    FirmwareRequestError,
+    WriteInit,
+    WriteInitError,
+    Write,
+    WriteError,
+    WriteComplete,
+    WriteCompleteError,
+    /// Programmed and running.
+    Operating,
+    Unknown,
+    /// State string this library does not know.
+    Other(String),
+}
+impl FpgaState {
+    /// Parse the contents of the `state` attribute.
+    pub fn parse(s: &str) -> Self {
+        match s.trim() {
+            "power off" => FpgaState::PowerOff,
+            "power up" => FpgaState::PowerUp,
+            "power down" => FpgaState::PowerDown,
+            "power error" | "power err" => FpgaState::PowerError,
+            "firmware request" => FpgaState::FirmwareRequest,
+            "firmware request error" => FpgaState::FirmwareRequestError,
+            "write init" => FpgaState::WriteInit,
+            "write init error" => FpgaState::WriteInitError,
+            "write" => FpgaState::Write,
+            "write error" => FpgaState::WriteError,
+            "write complete" => FpgaState::WriteComplete,
+            "write complete error" => FpgaState::WriteCompleteError,
+            "operating" => FpgaState::Operating,
+            "unknown" => FpgaState::Unknown,
+            other => FpgaState::Other(other.to_string()),
+        }
+    }
+    /// Whether programming failed.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            FpgaState::PowerError
+                | FpgaState::FirmwareRequestError
+                | FpgaState::WriteInitError
+                | FpgaState::WriteError
+                | FpgaState::WriteCompleteError
+        )
+    }
+}
+impl fmt::Display for FpgaState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FpgaState::Other(s) => f.write_str(s),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+/// Loading an image failed or did not finish in time.
+#[derive(Debug)]
+pub struct LoadError {
+    pub image: String,
+    /// Last state read from the manager.
+    pub state: FpgaState,
+    pub reason: String,
+}
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Loading FPGA image {:?} failed in state {}: {}",
+            self.image, self.state, self.reason
+        )
+    }
+}
+impl std::error::Error for LoadError {}
+
+type StateHook = Box<dyn FnMut(&FpgaState) + Send>;
+
+/// Handle to an FPGA manager device.
+pub struct FpgaManager {
+    root: PathBuf,
+    overlays: PathBuf,
+    firmware: PathBuf,
+    timeout: Duration,
+    on_state: Option<StateHook>,
+    // Unloads a failed overlay. Removing the configfs directory is enough;
+    // a fake tree also needs its attribute files removed.
+    remove_overlay: fn(&Path) -> io::Result<()>,
+}
+impl FpgaManager {
+    /// Manager `fpga0` with the kernel's default sysfs, configfs, and firmware
+    /// locations.
+    pub fn new() -> Self {
+        Self::with_roots(FPGA_MANAGER_ROOT, OVERLAYS_ROOT, FIRMWARE_ROOT)
+    }
+    /// Manager at `root`, with overlays under `overlays` and images under
+    /// `firmware`.
+    pub fn with_roots<P, Q, R>(root: P, overlays: Q, firmware: R) -> Self
+    where
+        P: Into<PathBuf>,
+        Q: Into<PathBuf>,
+        R: Into<PathBuf>,
+    {
+        Self {
+            root: root.into(),
+            overlays: overlays.into(),
+            firmware: firmware.into(),
+            timeout: Duration::from_secs(10),
+            on_state: None,
+            remove_overlay: |dir| fs::remove_dir(dir),
+        }
+    }
+    /// How long to wait for a load to reach `operating` (default 10 s).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    /// Call `f` with every state observed while loading.
+    pub fn on_state<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(&FpgaState) + Send + 'static,
+    {
+        self.on_state = Some(Box::new(f));
+        self
+    }
+    /// Current state of the FPGA.
+    pub fn state(&self) -> FpgaApiResult<FpgaState> {
+        Ok(FpgaState::parse(&fs::read_to_string(
+            self.root.join("state"),
+        )?))
+    }
+    /// Token for an FPGA that was programmed elsewhere (e.g. by the
+    /// bootloader), if it is operating.
+    pub fn already_loaded(&self) -> FpgaApiResult<Loaded> {
+        match self.state()? {
+            FpgaState::Operating => Ok(Loaded { overlay: None }),
+            state => Err(LoadError {
+                image: String::new(),
+                state,
+                reason: "FPGA is not operating".to_string(),
+            }
+            .into()),
+        }
+    }
+    /// Program `image` through the manager's `firmware` attribute. The image
+    /// is copied into the firmware directory if it is not there already.
+    pub fn load_firmware(&mut self, image: &Path) -> FpgaApiResult<Loaded> {
+        let name = self.install(image)?;
+        let reprogramming = self.state()? == FpgaState::Operating;
+        fs::write(self.root.join("firmware"), &name)?;
+        self.wait_operating(&name, !reprogramming)?;
+        Ok(Loaded { overlay: None })
+    }
+    /// Apply the device tree overlay `dtbo` as overlay `name`. The overlay's
+    /// `firmware-name` selects the image, which must be in the firmware
+    /// directory; the overlay itself is copied there if needed.
+    pub fn load_overlay(&mut self, name: &str, dtbo: &Path) -> FpgaApiResult<Loaded> {
+        let file = self.install(dtbo)?;
+        let dir = self.overlays.join(name);
+        fs::create_dir(&dir).map_err(|e| format!("Cannot create overlay {:?}: {}", dir, e))?;
+        match self.apply_overlay(&dir, &file) {
+            Ok(()) => Ok(Loaded { overlay: Some(dir) }),
+            Err(e) => {
+                // Removing the directory unloads a half-applied overlay.
+                let _ = (self.remove_overlay)(&dir);
+                Err(e)
+            }
+        }
+    }
+    fn apply_overlay(&mut self, dir: &Path, file: &str) -> FpgaApiResult<()> {
+        fs::write(dir.join("path"), file)?;
+        // The overlay may still be applying when the write returns, so the
+        // status is followed like the manager's state.
+        let start = Instant::now();
+        loop {
+            let status = match fs::read_to_string(dir.join("status")) {
+                Ok(status) => status.trim().to_string(),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+                Err(e) => return Err(e.into()),
+            };
+            if status == "applied" {
+                break;
+            }
+            if start.elapsed() >= self.timeout {
+                return Err(LoadError {
+                    image: file.to_string(),
+                    state: self.state()?,
+                    reason: format!("overlay status is {:?}", status),
+                }
+                .into());
+            }
+            std::thread::sleep(POLL_PERIOD);
+        }
+        // The overlay is applied, so the state is no longer the previous
+        // image's.
+        self.wait_operating(file, true)
+    }
+    /// File name of `path` in the firmware directory, copying it there if it
+    /// lives elsewhere.
+    fn install(&self, path: &Path) -> FpgaApiResult<String> {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| format!("Invalid image path {:?}", path))?;
+        let dest = self.firmware.join(name);
+        if path.parent() != Some(self.firmware.as_path()) {
+            fs::copy(path, &dest)?;
+        }
+        Ok(name.to_string())
+    }
+    /// Follow `state` until `operating`, an error state, or the timeout.
+    /// Unless `left`, an `operating` state is the previous image's until a
+    /// different state has been seen.
+    fn wait_operating(&mut self, image: &str, mut left: bool) -> FpgaApiResult<()> {
+        let start = Instant::now();
+        let mut last = None;
+        loop {
+            let state = self.state()?;
+            if last.as_ref() != Some(&state) {
+                if let Some(f) = self.on_state.as_mut() {
+                    f(&state);
+                }
+            }
+            if state == FpgaState::Operating {
+                if left {
+                    return Ok(());
+                }
+            } else {
+                left = true;
+            }
+            let reason = if state.is_error() {
+                "the FPGA manager reported an error"
+            } else if start.elapsed() >= self.timeout {
+                if left {
+                    "timed out waiting for the FPGA to operate"
+                } else {
+                    "timed out waiting for the FPGA to leave the previous image"
+                }
+            } else {
+                last = Some(state);
+                std::thread::sleep(POLL_PERIOD);
+                continue;
+            };
+            return Err(LoadError {
+                image: image.to_string(),
+                state,
+                reason: reason.to_string(),
+            }
+            .into());
+        }
+    }
+}
+impl Default for FpgaManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Proof that the FPGA has been programmed. Sessions on the fabric can only
+/// be opened through it. The image stays loaded when the token is dropped.
+pub struct Loaded {
+    // Configfs directory of the applied overlay.
+    overlay: Option<PathBuf>,
+}
+impl Loaded {
+    /// Take the session of `cell`. Fails if it was already taken.
+    pub fn take<S>(&self, cell: &RegionCell<S>) -> FpgaApiResult<S> {
+        cell.take()
+    }
+    /// Take the session of `cell` and run `check` on it (e.g.
+    /// `Identity::verify`) before handing it out. If the check fails, the
+    /// session is dropped and may be taken again.
+    pub fn take_checked<S, F>(&self, cell: &RegionCell<S>, check: F) -> FpgaApiResult<S>
+    where
+        F: FnOnce(&S) -> FpgaApiResult<()>,
+    {
+        cell.take_checked(check)
+    }
+    /// Remove the overlay (if the image was loaded with one).
+    pub fn unload(self) -> FpgaApiResult<()> {
+        match self.overlay {
+            Some(dir) => Ok(fs::remove_dir(dir)?),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::thread::JoinHandle;
+
+    use crate::region::{Region, RegionCell};
+    use crate::session::SimSesh;
+
+    /// Time between state changes of the fake kernel.
+    const STEP: Duration = Duration::from_millis(40);
+
+    /// Replace a file atomically, like a sysfs attribute changing.
+    fn set(root: &Path, rel: &str, contents: &str) {
+        let tmp = root.join("tmp").join(rel.replace('/', "_"));
+        fs::write(&tmp, contents).unwrap();
+        fs::rename(tmp, root.join(rel)).unwrap();
+    }
+
+    /// Fake sysfs, configfs and firmware directories, with images outside
+    /// of the firmware directory.
+    fn tree(state: &str) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for d in &["manager", "overlays", "firmware", "images", "tmp"] {
+            fs::create_dir(dir.path().join(d)).unwrap();
+        }
+        set(dir.path(), "manager/state", state);
+        fs::write(dir.path().join("images/soc.rbf"), "bitstream").unwrap();
+        fs::write(dir.path().join("images/soc.dtbo"), "overlay").unwrap();
+        dir
+    }
+
+    fn manager(root: &Path) -> FpgaManager {
+        let mut manager = FpgaManager::with_roots(
+            root.join("manager"),
+            root.join("overlays"),
+            root.join("firmware"),
+        )
+        .timeout(Duration::from_secs(5));
+        // Unlike configfs, the fake overlay directory holds real files.
+        manager.remove_overlay = |dir| fs::remove_dir_all(dir);
+        manager
+    }
+
+    /// Fake kernel: once `trigger` has been written, set the overlay
+    /// `status` (if any), then step the manager's state through `states`.
+    fn kernel(
+        root: &Path,
+        trigger: &'static str,
+        status: Option<(&'static str, &'static str)>,
+        states: &'static [&'static str],
+    ) -> JoinHandle<()> {
+        let root = root.to_path_buf();
+        std::thread::spawn(move || {
+            let start = Instant::now();
+            while fs::read_to_string(root.join(trigger))
+                .unwrap_or_default()
+                .is_empty()
+            {
+                assert!(start.elapsed() < Duration::from_secs(5));
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            if let Some((rel, status)) = status {
+                set(&root, rel, status);
+            }
+            for state in states {
+                std::thread::sleep(STEP);
+                set(&root, "manager/state", state);
+            }
+        })
+    }
+
+    fn load_error(err: crate::FpgaApiError) -> LoadError {
+        *err.downcast::<LoadError>().unwrap()
+    }
+
+    static CELL: RegionCell<SimSesh> =
+        RegionCell::new(Region::new("sim", 0, 8), |r| Ok(SimSesh::new(r.span)));
+
+    #[test]
+    fn firmware_load_reports_state_transitions() {
+        let root = tree("power off");
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        let mut manager =
+            manager(root.path()).on_state(move |s| log.lock().unwrap().push(s.clone()));
+        let kernel = kernel(
+            root.path(),
+            "manager/firmware",
+            None,
+            &["firmware request", "write init", "write", "operating"],
+        );
+        let loaded = manager
+            .load_firmware(&root.path().join("images/soc.rbf"))
+            .unwrap();
+        kernel.join().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(root.path().join("manager/firmware")).unwrap(),
+            "soc.rbf"
+        );
+        assert_eq!(
+            fs::read_to_string(root.path().join("firmware/soc.rbf")).unwrap(),
+            "bitstream"
+        );
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                FpgaState::PowerOff,
+                FpgaState::FirmwareRequest,
+                FpgaState::WriteInit,
+                FpgaState::Write,
+                FpgaState::Operating,
+            ]
+        );
+        assert!(loaded.take(&CELL).is_ok());
+        assert!(loaded.take(&CELL).is_err());
+        loaded.unload().unwrap();
+    }
+
+    #[test]
+    fn reload_waits_for_the_previous_image_to_stop() {
+        let root = tree("operating");
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        let mut manager =
+            manager(root.path()).on_state(move |s| log.lock().unwrap().push(s.clone()));
+        let kernel = kernel(
+            root.path(),
+            "manager/firmware",
+            None,
+            &["write", "operating"],
+        );
+        manager
+            .load_firmware(&root.path().join("images/soc.rbf"))
+            .unwrap();
+        kernel.join().unwrap();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![FpgaState::Operating, FpgaState::Write, FpgaState::Operating]
+        );
+    }
+
+    #[test]
+    fn reload_times_out_if_the_previous_image_keeps_operating() {
+        let root = tree("operating");
+        let mut manager = manager(root.path()).timeout(Duration::from_millis(50));
+        let err = load_error(
+            manager
+                .load_firmware(&root.path().join("images/soc.rbf"))
+                .err()
+                .unwrap(),
+        );
+        assert_eq!(err.state, FpgaState::Operating);
+        assert!(err.reason.contains("previous image"));
+    }
+
+    #[test]
+    fn error_state_fails_the_load() {
+        let root = tree("power off");
+        let mut manager = manager(root.path());
+        let kernel = kernel(
+            root.path(),
+            "manager/firmware",
+            None,
+            &["write init", "write init error"],
+        );
+        let err = manager
+            .load_firmware(&root.path().join("images/soc.rbf"))
+            .err()
+            .unwrap();
+        kernel.join().unwrap();
+        let err = load_error(err);
+        assert_eq!(err.image, "soc.rbf");
+        assert_eq!(err.state, FpgaState::WriteInitError);
+        assert!(err.reason.contains("error"));
+    }
+
+    #[test]
+    fn load_times_out_if_never_operating() {
+        let root = tree("write");
+        let mut manager = manager(root.path()).timeout(Duration::from_millis(50));
+        let start = Instant::now();
+        let err = load_error(
+            manager
+                .load_firmware(&root.path().join("images/soc.rbf"))
+                .err()
+                .unwrap(),
+        );
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(err.state, FpgaState::Write);
+        assert!(err.reason.contains("timed out"));
+    }
+
+    #[test]
+    fn already_loaded_requires_operating() {
+        let root = tree("operating");
+        assert!(manager(root.path()).already_loaded().is_ok());
+        set(root.path(), "manager/state", "power off");
+        let err = load_error(manager(root.path()).already_loaded().err().unwrap());
+        assert_eq!(err.state, FpgaState::PowerOff);
+    }
+
+    #[test]
+    fn overlay_load_applies_and_waits_for_operating() {
+        let root = tree("power off");
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        let mut manager =
+            manager(root.path()).on_state(move |s| log.lock().unwrap().push(s.clone()));
+        let kernel = kernel(
+            root.path(),
+            "overlays/point_nn/path",
+            Some(("overlays/point_nn/status", "applied")),
+            &["write", "operating"],
+        );
+        let loaded = manager
+            .load_overlay("point_nn", &root.path().join("images/soc.dtbo"))
+            .unwrap();
+        kernel.join().unwrap();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![FpgaState::PowerOff, FpgaState::Write, FpgaState::Operating]
+        );
+        let dir = root.path().join("overlays/point_nn");
+        assert_eq!(fs::read_to_string(dir.join("path")).unwrap(), "soc.dtbo");
+        assert!(root.path().join("firmware/soc.dtbo").exists());
+        // The overlay stays applied until it is unloaded.
+        drop(loaded);
+        assert!(dir.exists());
+    }
+
+    #[test]
+    fn unapplied_overlay_is_removed() {
+        let root = tree("operating");
+        let mut manager = manager(root.path()).timeout(Duration::from_millis(100));
+        let kernel = kernel(
+            root.path(),
+            "overlays/point_nn/path",
+            Some(("overlays/point_nn/status", "unapplied")),
+            &[],
+        );
+        let err = manager
+            .load_overlay("point_nn", &root.path().join("images/soc.dtbo"))
+            .err()
+            .unwrap();
+        kernel.join().unwrap();
+        let err = load_error(err);
+        assert_eq!(err.image, "soc.dtbo");
+        assert_eq!(err.reason, "overlay status is \"unapplied\"");
+        assert!(!root.path().join("overlays/point_nn").exists());
+    }
+
+    #[test]
+    fn existing_overlay_is_not_replaced() {
+        let root = tree("operating");
+        fs::create_dir(root.path().join("overlays/point_nn")).unwrap();
+        assert!(manager(root.path())
+            .load_overlay("point_nn", &root.path().join("images/soc.dtbo"))
+            .is_err());
+        assert!(root.path().join("overlays/point_nn").exists());
+    }
+}
@@ -0,0 +1,225 @@
+//! Bitstream identity and version checks.
+//!
+//! A stale or foreign bitstream answers register reads with garbage instead
+//! of failing. An `Identity` names an ID register (and optionally a version
+//! register) and the values the Rust register map was built for, so a session
+//! can be refused before it is used.
+
+use core::fmt;
+
+use crate::resources::Resource;
+use crate::traits::{ReadOnly, Session};
+#[cfg(not(feature = "std"))]
+use crate::FpgaApiError;
+use crate::FpgaApiResult;
+
+/// Register map version, packed in hardware as `major << 16 | minor`.
+///
+/// A bitstream is compatible if the major versions are equal and its minor
+/// version is at least the expected one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u16,
+    pub minor: u16,
+}
+impl Version {
+    pub const fn new(major: u16, minor: u16) -> Self {
+        Self { major, minor }
+    }
+    pub const fn from_bits(bits: u32) -> Self {
+        Self::new((bits >> 16) as u16, bits as u16)
+    }
+    pub const fn to_bits(self) -> u32 {
+        (self.major as u32) << 16 | self.minor as u32
+    }
+    /// Whether hardware at version `found` can be driven by a register map
+    /// built for `self`.
+    pub fn accepts(self, found: Version) -> bool {
+        found.major == self.major && found.minor >= self.minor
+    }
+}
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// The loaded bitstream is not the one the register map was built for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentityError {
+    WrongId { expected: u32, found: u32 },
+    IncompatibleVersion { expected: Version, found: Version },
+}
+impl fmt::Display for IdentityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdentityError::WrongId { expected, found } => write!(
+                f,
+                "Wrong bitstream loaded: expected ID {:#010x}, found {:#010x}",
+                expected, found
+            ),
+            IdentityError::IncompatibleVersion { expected, found } => write!(
+                f,
+                "Incompatible bitstream version: register map is for {}, found {}",
+                expected, found
+            ),
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for IdentityError {}
+#[cfg(not(feature = "std"))]
+impl From<IdentityError> for FpgaApiError {
+    fn from(e: IdentityError) -> Self {
+        FpgaApiError::from(match e {
+            IdentityError::WrongId { .. } => "Wrong bitstream loaded!",
+            IdentityError::IncompatibleVersion { .. } => "Incompatible bitstream version!",
+        })
+    }
+}
+
+/// ID (and optional version) registers and their expected values.
+///
+/// An Intel System ID core has no version register: besides the ID it only
+/// holds the time the system was generated, which changes on every Platform
+/// Designer run and cannot be known when the register map is written. For
+/// such cores the ID itself is the register map version and must be changed
+/// (in the `.qsys` and here) whenever the register map changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Identity {
+    pub id_offset: usize,
+    pub id: u32,
+    pub version: Option<(usize, Version)>,
+}
+impl Identity {
+    /// Expect `id` in the 32-bit register at `id_offset`.
+    pub const fn new(id_offset: usize, id: u32) -> Self {
+        Self {
+            id_offset,
+            id,
+            version: None,
+        }
+    }
+    /// Also expect a compatible version in the register at `offset`.
+    pub const fn with_version(mut self, offset: usize, version: Version) -> Self {
+        self.version = Some((offset, version));
+        self
+    }
+    /// Identity of an Intel System ID (`altera_avalon_sysid_qsys`) core,
+    /// whose ID register is at offset 0.
+    pub const fn sysid(id: u32) -> Self {
+        Self::new(0, id)
+    }
+    /// Read the ID and version registers and compare them with the expected
+    /// values.
+    pub fn verify<S: Session>(&self, sesh: &S) -> FpgaApiResult<()> {
+        let found = sesh.read(&Resource::<u32, ReadOnly>::new("id", self.id_offset))?;
+        if found != self.id {
+            return Err(IdentityError::WrongId {
+                expected: self.id,
+                found,
+            }
+            .into());
+        }
+        if let Some((offset, expected)) = self.version {
+            let bits = sesh.read(&Resource::<u32, ReadOnly>::new("version", offset))?;
+            let found = Version::from_bits(bits);
+            if !expected.accepts(found) {
+                return Err(IdentityError::IncompatibleVersion { expected, found }.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::session::SimSesh;
+    use crate::traits::ReadWrite;
+
+    const ID: u32 = 0xDE10_DE10;
+    const V1_2: Version = Version::new(1, 2);
+
+    /// System ID block with an extra version register at offset 8.
+    fn sesh(id: u32, version: Version) -> SimSesh {
+        let mut sesh = SimSesh::new(12);
+        sesh.write(&Resource::<u32, ReadWrite>::new("id", 0), id)
+            .unwrap();
+        sesh.write(
+            &Resource::<u32, ReadWrite>::new("version", 8),
+            version.to_bits(),
+        )
+        .unwrap();
+        sesh
+    }
+
+    fn identity_error(res: FpgaApiResult<()>) -> IdentityError {
+        *res.unwrap_err().downcast::<IdentityError>().unwrap()
+    }
+
+    #[test]
+    fn version_bits_round_trip() {
+        assert_eq!(V1_2.to_bits(), 0x0001_0002);
+        assert_eq!(Version::from_bits(0x0001_0002), V1_2);
+        assert_eq!(V1_2.to_string(), "1.2");
+    }
+
+    #[test]
+    fn matching_id_passes() {
+        Identity::sysid(ID).verify(&sesh(ID, V1_2)).unwrap();
+    }
+
+    #[test]
+    fn wrong_id_is_rejected() {
+        assert_eq!(
+            identity_error(Identity::sysid(ID).verify(&sesh(0x1234_5678, V1_2))),
+            IdentityError::WrongId {
+                expected: ID,
+                found: 0x1234_5678,
+            }
+        );
+    }
+
+    #[test]
+    fn newer_minor_version_is_accepted() {
+        let identity = Identity::sysid(ID).with_version(8, V1_2);
+        identity.verify(&sesh(ID, V1_2)).unwrap();
+        identity.verify(&sesh(ID, Version::new(1, 7))).unwrap();
+    }
+
+    #[test]
+    fn older_minor_version_is_rejected() {
+        let identity = Identity::sysid(ID).with_version(8, V1_2);
+        assert_eq!(
+            identity_error(identity.verify(&sesh(ID, Version::new(1, 1)))),
+            IdentityError::IncompatibleVersion {
+                expected: V1_2,
+                found: Version::new(1, 1),
+            }
+        );
+    }
+
+    #[test]
+    fn different_major_version_is_rejected() {
+        let identity = Identity::sysid(ID).with_version(8, V1_2);
+        for found in [Version::new(0, 9), Version::new(2, 0), Version::new(2, 5)] {
+            assert_eq!(
+                identity_error(identity.verify(&sesh(ID, found))),
+                IdentityError::IncompatibleVersion {
+                    expected: V1_2,
+                    found,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn wrong_id_is_reported_before_the_version() {
+        let identity = Identity::sysid(ID).with_version(8, V1_2);
+        assert!(matches!(
+            identity_error(identity.verify(&sesh(0, Version::new(9, 0)))),
+            IdentityError::WrongId { .. }
+        ));
+    }
+}
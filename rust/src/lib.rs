@@ -1,66 +1,144 @@
 //! FPGA interaction library for 2020 Scale by the Bay talk.
+//!
+//! The `Data`/`Readable`/`Writable`/`Session` traits and `Resource` are
+//! available without the `std` feature so register definitions can be shared
+//! with bare-metal firmware.
 
-use std::fs::OpenOptions;
-use std::path::Path;
-use std::sync::Mutex;
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use lazy_static::lazy_static;
-use memmap::MmapOptions;
+#[cfg(feature = "alloc")]
+extern crate alloc;
 
-pub type FpgaApiError = Box<dyn std::error::Error>;
-pub type FpgaApiResult<T> = std::result::Result<T, FpgaApiError>;
+#[cfg(feature = "std")]
+pub type FpgaApiError = Box<dyn std::error::Error + Send + Sync>;
+pub type FpgaApiResult<T> = core::result::Result<T, FpgaApiError>;
 
+/// Error for `no_std` builds, carrying a static description.
+#[cfg(not(feature = "std"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FpgaApiError(&'static str);
+#[cfg(not(feature = "std"))]
+impl From<&'static str> for FpgaApiError {
+    fn from(msg: &'static str) -> Self {
+        FpgaApiError(msg)
+    }
+}
+#[cfg(not(feature = "std"))]
+impl core::fmt::Display for FpgaApiError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+#[cfg(feature = "async")]
+pub mod async_session;
+#[cfg(feature = "std")]
+pub mod avalon;
+pub mod brand;
+#[cfg(feature = "std")]
+pub mod clash;
 pub mod data;
+#[cfg(feature = "std")]
+pub mod devicetree;
+#[cfg(feature = "std")]
+pub mod fpga_manager;
+pub mod identity;
+#[cfg(feature = "alloc")]
+pub mod lifecycle;
+pub mod peripherals;
+pub mod point_nn;
+#[cfg(feature = "std")]
+pub mod poll;
+pub mod region;
+pub mod regmap;
 pub mod resources;
+#[rustfmt::skip]
+pub mod run_network;
+#[cfg(feature = "std")]
+pub mod safe_state;
 pub mod session;
+#[cfg(feature = "alloc")]
+pub mod shadow;
+#[cfg(feature = "std")]
+pub mod shared;
 pub mod traits;
+#[cfg(feature = "std")]
+pub mod transaction;
+#[cfg(feature = "std")]
+pub mod uio;
+#[cfg(feature = "alloc")]
+pub mod validate;
+#[cfg(feature = "std")]
+pub mod verify;
+#[cfg(feature = "std")]
+pub mod watchdog;
 
+#[cfg(feature = "std")]
 use session::MmapSesh;
+#[cfg(feature = "std")]
+use shared::SharedSesh;
 
-struct Fpga(Option<MmapSesh>);
-impl Fpga {
-    fn take(&mut self) -> MmapSesh {
-        let sesh = self.0.take();
-        sesh.expect("It is forbidden to create more than one FPGA session!")
-    }
-}
+#[cfg(feature = "std")]
+use fpga_manager::Loaded;
+use identity::Identity;
+#[cfg(feature = "std")]
+use peripherals::Peripherals;
+#[cfg(feature = "std")]
+use region::RegionCell;
+use region::{Region, H2F_BRIDGE_BASE, LW_H2F_BRIDGE_BASE};
+use regmap::RegisterMap;
 
-const POINT_NN_BASE: u64 = 0xC002_0000;
-const POINT_NN_SPAN: usize = 64;
-const POINT_NN_END: u64 = POINT_NN_BASE + POINT_NN_SPAN as u64 - 1;
+/// Point quadrant classifier, at `0x0002_0000` behind the HPS-to-FPGA bridge.
+pub const POINT_NN_REGION: Region = Region::new("point_nn", H2F_BRIDGE_BASE + 0x2_0000, 64);
 
-pub const POINT_NN_INPUT_VECTOR_OFFSET: usize = 0;
-pub const POINT_NN_OUTPUT_CLASS_OFFSET: usize = 8;
+pub const POINT_NN_INPUT_VECTOR_OFFSET: usize = run_network::IN_OFFSET;
+pub const POINT_NN_OUTPUT_CLASS_OFFSET: usize = run_network::OUT_OFFSET;
 
-lazy_static! {
-    /// Global FPGA handle to be accessed through singleton pattern.
-    static ref POINT_NN_FPGA: Mutex<Fpga> = {
-        let path = Path::new("/dev/mem");
-        let file = match OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(false)
-            .open(&path)
-        {
-            Ok(f) => f,
-            Err(e) => panic!(
-                "ERROR trying to open memory-mapped file: {:?}: {}",
-                &path, e
-            ),
-        };
-        let fpga_map = match unsafe { MmapOptions::new().offset(POINT_NN_BASE).len(POINT_NN_SPAN).map_mut(&file) } {
-            Ok(map) => map,
-            Err(e) => panic!("ERROR trying to initialize memory-mapped file: {}", e),
-        };
-        let session = match MmapSesh::new(fpga_map) {
-            Ok(s) => s,
-            Err(e) => panic!("ERROR trying to initialize FPGA session: {}", e),
-        };
-        Mutex::new(Fpga(Some(session)))
+/// Register map of the point classifier: the registers generated from the
+/// Clash `runNetwork` entity (`run_network`), sized by the Rust types
+/// accessing them. `quartus-project/runNetworkTop.sv` and
+/// `runNetworkAVS_hw.tcl` are generated from it by the `point_nn_wrapper`
+/// binary.
+pub const POINT_NN_REGISTER_MAP: RegisterMap =
+    RegisterMap::new("point_nn", run_network::RUN_NETWORK_REGISTER_MAP.registers);
+
+/// System ID core, at `0x0001_0030` behind the lightweight bridge.
+pub const SYSTEM_ID_REGION: Region = Region::new("system_id", LW_H2F_BRIDGE_BASE + 0x1_0030, 8);
+/// ID of the bitstream this register map was built for (`system_id` in
+/// `quartus-project/soc_system.qsys`). Bump it in both places whenever the
+/// register map changes incompatibly; see `Identity`.
+pub const SYSTEM_ID: Identity = Identity::sysid(0xDE10_DE10);
+
+/// Check that the loaded bitstream carries `SYSTEM_ID`.
+#[cfg(feature = "std")]
+pub fn verify_system_id() -> FpgaApiResult<()> {
+    SYSTEM_ID.verify(&region::open_dev_mem(&SYSTEM_ID_REGION)?)
+}
+
+/// Global point classifier handle, mapped through `/dev/mem`. Only reachable
+/// through the `peripherals::PointNnCore` token, which checks the bitstream
+/// identity before handing the session out.
+#[cfg(feature = "std")]
+pub(crate) static POINT_NN: RegionCell<MmapSesh> =
+    RegionCell::new(POINT_NN_REGION, region::open_dev_mem);
+
+/// Take FPGA session singleton through `Peripherals::take`, once the FPGA is
+/// `loaded`. User must uphold invariant to only call once (and not take the
+/// peripherals elsewhere) to avoid a runtime panic.
+#[cfg(feature = "std")]
+pub fn take_fpga_session(loaded: &Loaded) -> MmapSesh {
+    let core = match Peripherals::take() {
+        Some(p) => p.point_nn.core,
+        None => panic!("ERROR trying to initialize FPGA session: peripherals were already taken"),
     };
+    match core.open(loaded) {
+        Ok(s) => s,
+        Err(e) => panic!("ERROR trying to initialize FPGA session: {}", e),
+    }
 }
-/// Take FPGA session singleton. User must uphold invariant to only call once
-/// to avoid a runtime panic.
-pub fn take_fpga_session() -> MmapSesh {
-    POINT_NN_FPGA.lock().unwrap().take()
+/// Take FPGA session singleton as a handle that can be shared between
+/// threads. The same invariant as `take_fpga_session` applies.
+#[cfg(feature = "std")]
+pub fn take_shared_fpga_session(loaded: &Loaded) -> SharedSesh<MmapSesh> {
+    SharedSesh::new(take_fpga_session(loaded))
 }
@@ -0,0 +1,419 @@
+//! Typestate for the FPGA session lifecycle.
+//!
+//! `Unconfigured` --`configure`--> `Ready` --`start`--> `Running`
+//! --`stop`--> `Stopped` --`start`--> `Running`. Each transition runs the
+//! matching user-supplied hook on the wrapped session. If a hook fails, the
+//! session is handed back in the state it was in. Registers wrapped in
+//! `WhileRunning` can only be accessed in the `Running` state:
+//!
+//! ```
+//! use sbtb::lifecycle::{Hooks, Lifecycle, WhileRunning};
+//! use sbtb::region::Region;
+//! use sbtb::resources::Resource;
+//! use sbtb::session::SimSesh;
+//! use sbtb::traits::ReadWrite;
+//!
+//! const SIM: Region = Region::new("sim", 0, 4);
+//!
+//! let speed = WhileRunning::new(Resource::<u32, ReadWrite>::in_region(&SIM, "speed", 0)?);
+//! let ready = Lifecycle::new(SimSesh::new(4), Hooks::new()).configure()?;
+//! let mut running = ready.start()?;
+//! running.write_running(&speed, 3)?;
+//! assert_eq!(running.read_running(&speed)?, 3);
+//! # Ok::<(), sbtb::FpgaApiError>(())
+//! ```
+//!
+//! Such a register cannot be written before the session is started:
+//!
+//! ```compile_fail
+//! # use sbtb::lifecycle::{Hooks, Lifecycle, WhileRunning};
+//! # use sbtb::region::Region;
+//! # use sbtb::resources::Resource;
+//! # use sbtb::session::SimSesh;
+//! # use sbtb::traits::ReadWrite;
+//! # const SIM: Region = Region::new("sim", 0, 4);
+//! let speed = WhileRunning::new(Resource::<u32, ReadWrite>::in_region(&SIM, "speed", 0)?);
+//! let mut ready = Lifecycle::new(SimSesh::new(4), Hooks::new()).configure()?;
+//! ready.write(&speed, 3)?;
+//! # Ok::<(), sbtb::FpgaApiError>(())
+//! ```
+//!
+//! nor read while the session is `Ready`
+//!
+//! ```compile_fail
+//! # use sbtb::lifecycle::{Hooks, Lifecycle, WhileRunning};
+//! # use sbtb::region::Region;
+//! # use sbtb::resources::Resource;
+//! # use sbtb::session::SimSesh;
+//! # use sbtb::traits::ReadWrite;
+//! # const SIM: Region = Region::new("sim", 0, 4);
+//! let speed = WhileRunning::new(Resource::<u32, ReadWrite>::in_region(&SIM, "speed", 0)?);
+//! let ready = Lifecycle::new(SimSesh::new(4), Hooks::new()).configure()?;
+//! ready.read(&speed)?;
+//! # Ok::<(), sbtb::FpgaApiError>(())
+//! ```
+//!
+//! or `Stopped`:
+//!
+//! ```compile_fail
+//! # use sbtb::lifecycle::{Hooks, Lifecycle, WhileRunning};
+//! # use sbtb::region::Region;
+//! # use sbtb::resources::Resource;
+//! # use sbtb::session::SimSesh;
+//! # use sbtb::traits::ReadWrite;
+//! # const SIM: Region = Region::new("sim", 0, 4);
+//! let speed = WhileRunning::new(Resource::<u32, ReadWrite>::in_region(&SIM, "speed", 0)?);
+//! let ready = Lifecycle::new(SimSesh::new(4), Hooks::new()).configure()?;
+//! let stopped = ready.start()?.stop()?;
+//! stopped.read(&speed)?;
+//! # Ok::<(), sbtb::FpgaApiError>(())
+//! ```
+
+use alloc::boxed::Box;
+use core::fmt;
+use core::marker::PhantomData;
+
+use crate::traits::{Readable, Session, Writable};
+use crate::{FpgaApiError, FpgaApiResult};
+
+/// Trait to implement typestates for the session lifecycle.
+pub trait LifecycleState {
+    /// Whether the hardware is running in this state.
+    const RUNNING: bool;
+}
+/// Typestate before configuration (runtime uninhabitable).
+pub enum Unconfigured {}
+impl LifecycleState for Unconfigured {
+    const RUNNING: bool = false;
+}
+/// Typestate for a configured but idle session (runtime uninhabitable).
+pub enum Ready {}
+impl LifecycleState for Ready {
+    const RUNNING: bool = false;
+}
+/// Typestate for a running session (runtime uninhabitable).
+pub enum Running {}
+impl LifecycleState for Running {
+    const RUNNING: bool = true;
+}
+/// Typestate for a stopped session (runtime uninhabitable).
+pub enum Stopped {}
+impl LifecycleState for Stopped {
+    const RUNNING: bool = false;
+}
+
+type Hook<S> = Box<dyn FnMut(&mut S) -> FpgaApiResult<()>>;
+
+/// Initialization and shutdown sequences run on lifecycle transitions.
+pub struct Hooks<S> {
+    configure: Option<Hook<S>>,
+    start: Option<Hook<S>>,
+    stop: Option<Hook<S>>,
+}
+impl<S> Hooks<S> {
+    pub fn new() -> Self {
+        Self {
+            configure: None,
+            start: None,
+            stop: None,
+        }
+    }
+    /// Run `f` when moving from `Unconfigured` to `Ready`.
+    pub fn on_configure<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(&mut S) -> FpgaApiResult<()> + 'static,
+    {
+        self.configure = Some(Box::new(f));
+        self
+    }
+    /// Run `f` when moving to `Running`. If `f` fails, the stop hook runs to
+    /// undo a partial start.
+    pub fn on_start<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(&mut S) -> FpgaApiResult<()> + 'static,
+    {
+        self.start = Some(Box::new(f));
+        self
+    }
+    /// Run `f` when moving from `Running` to `Stopped`, including when a
+    /// running session is dropped.
+    pub fn on_stop<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(&mut S) -> FpgaApiResult<()> + 'static,
+    {
+        self.stop = Some(Box::new(f));
+        self
+    }
+}
+impl<S> Default for Hooks<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn run_hook<S>(hook: &mut Option<Hook<S>>, sesh: &mut S) -> FpgaApiResult<()> {
+    match hook {
+        Some(f) => f(sesh),
+        None => Ok(()),
+    }
+}
+
+type HookField<S> = fn(&mut Hooks<S>) -> &mut Option<Hook<S>>;
+
+/// A failed lifecycle transition, with the session back in its previous
+/// state `St`. Converting into `FpgaApiError` drops the session.
+pub struct TransitionError<S: Session, St: LifecycleState> {
+    /// Error of the failed hook.
+    pub error: FpgaApiError,
+    /// The session in the state the transition started from.
+    pub sesh: Lifecycle<S, St>,
+}
+impl<S: Session, St: LifecycleState> fmt::Debug for TransitionError<S, St> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransitionError")
+            .field("error", &self.error)
+            .finish()
+    }
+}
+impl<S: Session, St: LifecycleState> fmt::Display for TransitionError<S, St> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Lifecycle transition failed: {}", self.error)
+    }
+}
+impl<S: Session, St: LifecycleState> From<TransitionError<S, St>> for FpgaApiError {
+    fn from(e: TransitionError<S, St>) -> Self {
+        e.error
+    }
+}
+
+/// Result of moving a session from state `From` to state `To`.
+pub type TransitionResult<S, From, To> = Result<Lifecycle<S, To>, TransitionError<S, From>>;
+
+/// Register that may only be accessed while the session is `Running`. It is
+/// neither `Readable` nor `Writable` itself, so the accessors of the other
+/// states reject it.
+pub struct WhileRunning<R>(R);
+impl<R> WhileRunning<R> {
+    pub fn new(resource: R) -> Self {
+        Self(resource)
+    }
+}
+/// Session `S` in lifecycle state `St`.
+pub struct Lifecycle<S: Session, St: LifecycleState> {
+    // Only `None` while moving into the next state.
+    inner: Option<(S, Hooks<S>)>,
+    _st: PhantomData<St>,
+}
+impl<S: Session, St: LifecycleState> Lifecycle<S, St> {
+    fn from_parts(sesh: S, hooks: Hooks<S>) -> Self {
+        Self {
+            inner: Some((sesh, hooks)),
+            _st: PhantomData,
+        }
+    }
+    fn sesh(&self) -> &S {
+        &self
+            .inner
+            .as_ref()
+            .expect("Lifecycle session is missing!")
+            .0
+    }
+    fn sesh_mut(&mut self) -> &mut S {
+        &mut self
+            .inner
+            .as_mut()
+            .expect("Lifecycle session is missing!")
+            .0
+    }
+    /// Run `hook` and move to state `To`. On failure, run `undo` (if any) and
+    /// hand the session back in its current state.
+    fn transition<To: LifecycleState>(
+        mut self,
+        hook: HookField<S>,
+        undo: Option<HookField<S>>,
+    ) -> TransitionResult<S, St, To> {
+        let (sesh, hooks) = self.inner.as_mut().expect("Lifecycle session is missing!");
+        if let Err(error) = run_hook(hook(hooks), sesh) {
+            if let Some(undo) = undo {
+                // The hook's error is the one worth reporting.
+                let _ = run_hook(undo(hooks), sesh);
+            }
+            return Err(TransitionError { error, sesh: self });
+        }
+        let (sesh, hooks) = self.inner.take().expect("Lifecycle session is missing!");
+        Ok(Lifecycle::from_parts(sesh, hooks))
+    }
+    /// Read a readable resource (allowed in every state).
+    pub fn read<R: Readable>(&self, resource: &R) -> FpgaApiResult<R::Value> {
+        self.sesh().read(resource)
+    }
+}
+impl<S: Session> Lifecycle<S, Unconfigured> {
+    pub fn new(sesh: S, hooks: Hooks<S>) -> Self {
+        Self::from_parts(sesh, hooks)
+    }
+    /// Run the configure hook.
+    pub fn configure(self) -> TransitionResult<S, Unconfigured, Ready> {
+        self.transition(|h| &mut h.configure, None)
+    }
+}
+impl<S: Session> Lifecycle<S, Ready> {
+    /// Write a configuration register.
+    pub fn write<R: Writable>(&mut self, resource: &R, val: R::Value) -> FpgaApiResult<()> {
+        self.sesh_mut().write(resource, val)
+    }
+    /// Run the start hook.
+    pub fn start(self) -> TransitionResult<S, Ready, Running> {
+        self.transition(|h| &mut h.start, Some(|h| &mut h.stop))
+    }
+}
+impl<S: Session> Lifecycle<S, Running> {
+    /// Write a writable resource.
+    pub fn write<R: Writable>(&mut self, resource: &R, val: R::Value) -> FpgaApiResult<()> {
+        self.sesh_mut().write(resource, val)
+    }
+    /// Write a register that is only legal while running.
+    pub fn write_running<R: Writable>(
+        &mut self,
+        resource: &WhileRunning<R>,
+        val: R::Value,
+    ) -> FpgaApiResult<()> {
+        self.sesh_mut().write(&resource.0, val)
+    }
+    /// Read a register that is only meaningful while running.
+    pub fn read_running<R: Readable>(&self, resource: &WhileRunning<R>) -> FpgaApiResult<R::Value> {
+        self.sesh().read(&resource.0)
+    }
+    /// Run the stop hook. On failure the session stays `Running`, so the stop
+    /// hook runs again when it is dropped.
+    pub fn stop(self) -> TransitionResult<S, Running, Stopped> {
+        self.transition(|h| &mut h.stop, None)
+    }
+}
+impl<S: Session> Lifecycle<S, Stopped> {
+    /// Run the start hook again.
+    pub fn start(self) -> TransitionResult<S, Stopped, Running> {
+        self.transition(|h| &mut h.start, Some(|h| &mut h.stop))
+    }
+    /// Leave the lifecycle and return the session.
+    pub fn into_inner(mut self) -> S {
+        self.inner.take().expect("Lifecycle session is missing!").0
+    }
+}
+impl<S: Session, St: LifecycleState> Drop for Lifecycle<S, St> {
+    fn drop(&mut self) {
+        // A running session is stopped even when dropped early (or during a
+        // panic). There is no caller left to report a failure to.
+        if St::RUNNING {
+            if let Some((sesh, hooks)) = self.inner.as_mut() {
+                let _ = run_hook(&mut hooks.stop, sesh);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{rc::Rc, string::ToString, vec, vec::Vec};
+    use core::cell::RefCell;
+
+    use crate::resources::Resource;
+    use crate::session::SimSesh;
+    use crate::traits::ReadWrite;
+
+    type Log = Rc<RefCell<Vec<&'static str>>>;
+
+    /// Hooks that record their name, and fail if it is in `failing`.
+    fn hooks(log: &Log, failing: &'static [&'static str]) -> Hooks<SimSesh> {
+        let hook = |name: &'static str| {
+            let log = Rc::clone(log);
+            move |_: &mut SimSesh| {
+                log.borrow_mut().push(name);
+                if failing.contains(&name) {
+                    Err(FpgaApiError::from("hook failed"))
+                } else {
+                    Ok(())
+                }
+            }
+        };
+        Hooks::new()
+            .on_configure(hook("configure"))
+            .on_start(hook("start"))
+            .on_stop(hook("stop"))
+    }
+
+    #[test]
+    fn hooks_run_in_transition_order() {
+        let log = Log::default();
+        let reg = Resource::<u32, ReadWrite>::new("reg", 0);
+        let mut ready = Lifecycle::new(SimSesh::new(4), hooks(&log, &[]))
+            .configure()
+            .unwrap();
+        ready.write(&reg, 7).unwrap();
+        let stopped = ready.start().unwrap().stop().unwrap();
+        let sesh = stopped.start().unwrap().stop().unwrap().into_inner();
+        assert_eq!(sesh.read(&reg).unwrap(), 7);
+        assert_eq!(
+            *log.borrow(),
+            vec!["configure", "start", "stop", "start", "stop"]
+        );
+    }
+
+    #[test]
+    fn dropping_a_running_session_stops_it() {
+        let log = Log::default();
+        let running = Lifecycle::new(SimSesh::new(4), hooks(&log, &[]))
+            .configure()
+            .unwrap()
+            .start()
+            .unwrap();
+        drop(running);
+        assert_eq!(*log.borrow(), vec!["configure", "start", "stop"]);
+
+        // Sessions that are not running are dropped without stopping.
+        log.borrow_mut().clear();
+        drop(Lifecycle::new(SimSesh::new(4), hooks(&log, &[])).configure());
+        assert_eq!(*log.borrow(), vec!["configure"]);
+    }
+
+    #[test]
+    fn failed_start_stops_and_returns_the_ready_session() {
+        let log = Log::default();
+        let ready = Lifecycle::new(SimSesh::new(4), hooks(&log, &["start"]))
+            .configure()
+            .unwrap();
+        let err = ready.start().err().unwrap();
+        assert_eq!(err.error.to_string(), "hook failed");
+        assert_eq!(*log.borrow(), vec!["configure", "start", "stop"]);
+        // Dropping the returned `Ready` session does not stop it again.
+        drop(err.sesh);
+        assert_eq!(log.borrow().len(), 3);
+    }
+
+    #[test]
+    fn failed_configure_returns_the_unconfigured_session() {
+        let log = Log::default();
+        let err = Lifecycle::new(SimSesh::new(4), hooks(&log, &["configure"]))
+            .configure()
+            .err()
+            .unwrap();
+        assert_eq!(*log.borrow(), vec!["configure"]);
+        // The session can be retried.
+        assert!(err.sesh.configure().is_err());
+        assert_eq!(*log.borrow(), vec!["configure", "configure"]);
+    }
+
+    #[test]
+    fn failed_stop_keeps_running_and_stops_on_drop() {
+        let log = Log::default();
+        let running = Lifecycle::new(SimSesh::new(4), hooks(&log, &["stop"]))
+            .configure()
+            .unwrap()
+            .start()
+            .unwrap();
+        let err = running.stop().err().unwrap();
+        drop(err.sesh);
+        assert_eq!(*log.borrow(), vec!["configure", "start", "stop", "stop"]);
+    }
+}
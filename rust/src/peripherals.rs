@@ -0,0 +1,127 @@
+//! Zero-sized ownership tokens for FPGA cores and their registers.
+//!
+//! `Peripherals::take` hands out every token exactly once. Tokens are neither
+//! `Clone` nor `Copy` and can only be created here, so giving the same core
+//! or register to two owners is a compile-time move error instead of a
+//! runtime `take` panic:
+//!
+//! ```compile_fail,E0382
+//! use sbtb::peripherals::{Peripherals, PointNnCore};
+//!
+//! fn own(_core: PointNnCore) {}
+//!
+//! let p = Peripherals::take().unwrap();
+//! own(p.point_nn.core);
+//! own(p.point_nn.core); // use of moved value
+//! ```
+//!
+//! The point classifier's session is only handed out through its
+//! `PointNnCore` token, once the FPGA is `Loaded`; `crate::take_fpga_session`
+//! takes the tokens itself.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use fixed::types::I7F25;
+
+use crate::point_nn::Point;
+use crate::traits::{LittleEndian, Readable, Writable};
+use crate::{POINT_NN_INPUT_VECTOR_OFFSET, POINT_NN_OUTPUT_CLASS_OFFSET};
+
+#[cfg(feature = "std")]
+use crate::fpga_manager::Loaded;
+#[cfg(feature = "std")]
+use crate::session::MmapSesh;
+#[cfg(feature = "std")]
+use crate::FpgaApiResult;
+
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// All FPGA cores of the bitstream.
+pub struct Peripherals {
+    pub point_nn: PointNn,
+}
+impl Peripherals {
+    /// Take all tokens. Returns `None` on every call after the first.
+    pub fn take() -> Option<Self> {
+        if TAKEN.swap(true, Ordering::SeqCst) {
+            None
+        } else {
+            Some(Self {
+                point_nn: PointNn {
+                    core: PointNnCore { _p: () },
+                    input_vector: InputVector { _p: () },
+                    output_class: OutputClass { _p: () },
+                },
+            })
+        }
+    }
+}
+
+/// Tokens of the point quadrant classifier.
+pub struct PointNn {
+    pub core: PointNnCore,
+    pub input_vector: InputVector,
+    pub output_class: OutputClass,
+}
+
+/// Ownership of the point classifier's address region.
+pub struct PointNnCore {
+    _p: (),
+}
+impl PointNnCore {
+    /// Open the classifier session through `/dev/mem`, consuming the token.
+    /// Fails if the bitstream identity does not match or the mapping fails.
+    #[cfg(feature = "std")]
+    pub fn open(self, loaded: &Loaded) -> FpgaApiResult<MmapSesh> {
+        loaded.take_checked(&crate::POINT_NN, |_| crate::verify_system_id())
+    }
+}
+
+/// Input vector register of the point classifier (read/write).
+pub struct InputVector {
+    _p: (),
+}
+impl core::fmt::Display for InputVector {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "Input Points at byte offset {}",
+            POINT_NN_INPUT_VECTOR_OFFSET
+        )
+    }
+}
+impl Readable for InputVector {
+    type Value = Point;
+    type Order = LittleEndian;
+    fn byte_offset(&self) -> usize {
+        POINT_NN_INPUT_VECTOR_OFFSET
+    }
+}
+impl Writable for InputVector {
+    type Value = Point;
+    type Order = LittleEndian;
+    fn byte_offset(&self) -> usize {
+        POINT_NN_INPUT_VECTOR_OFFSET
+    }
+}
+
+/// Output classification register of the point classifier (read-only).
+pub struct OutputClass {
+    _p: (),
+}
+impl core::fmt::Display for OutputClass {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "Output Classification Register at byte offset {}",
+            POINT_NN_OUTPUT_CLASS_OFFSET
+        )
+    }
+}
+impl Readable for OutputClass {
+    type Value = I7F25;
+    type Order = LittleEndian;
+    fn byte_offset(&self) -> usize {
+        POINT_NN_OUTPUT_CLASS_OFFSET
+    }
+}
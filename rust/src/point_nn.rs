@@ -0,0 +1,235 @@
+//! Typed input vector for the point quadrant classifier.
+//!
+//! The classifier takes two `SFixed 7 25` coordinates, which only cover
+//! `[-64, 64)`. Floats are converted under an explicit `Conversion` policy so
+//! that out-of-range data never silently turns into a wrong classification.
+
+use fixed::types::I7F25;
+
+use crate::run_network::In;
+use crate::traits::Data;
+#[cfg(not(feature = "std"))]
+use crate::FpgaApiError;
+use crate::FpgaApiResult;
+
+/// Policy for converting floats that do not fit into `I7F25`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conversion {
+    /// Reject the value with an `OutOfRange` error.
+    Error,
+    /// Clamp the value to the nearest representable `I7F25`.
+    Saturate,
+    /// Wrap the value around the `I7F25` range.
+    Wrap,
+}
+
+/// Coordinate of a classifier input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+}
+
+/// A float coordinate that is NaN or outside of the `I7F25` range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutOfRange {
+    pub axis: Axis,
+    pub value: f32,
+}
+impl core::fmt::Display for OutOfRange {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{:?} input {} is outside of the I7F25 range [{}, {}]",
+            self.axis,
+            self.value,
+            I7F25::MIN,
+            I7F25::MAX
+        )
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for OutOfRange {}
+#[cfg(not(feature = "std"))]
+impl From<OutOfRange> for FpgaApiError {
+    fn from(_: OutOfRange) -> Self {
+        FpgaApiError::from("Input is outside of the I7F25 range!")
+    }
+}
+
+/// Convert a float coordinate to `I7F25` under the given policy. NaN is always
+/// rejected since no policy gives it a meaningful value.
+pub fn to_fixed(value: f32, axis: Axis, policy: Conversion) -> Result<I7F25, OutOfRange> {
+    let err = OutOfRange { axis, value };
+    if value.is_nan() {
+        return Err(err);
+    }
+    match policy {
+        Conversion::Error => I7F25::checked_from_num(value).ok_or(err),
+        Conversion::Saturate => Ok(I7F25::saturating_from_num(value)),
+        Conversion::Wrap => I7F25::checked_from_num(value)
+            .or_else(|| value.is_finite().then(|| I7F25::wrapping_from_num(value)))
+            .ok_or(err),
+    }
+}
+
+/// Input vector of the point classifier (Clash `Vec 2 (SFixed 7 25)`), laid
+/// out like the generated `run_network::In`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Point {
+    pub x: I7F25,
+    pub y: I7F25,
+}
+impl Point {
+    pub fn new(x: I7F25, y: I7F25) -> Self {
+        Self { x, y }
+    }
+    /// Convert float coordinates under the given policy.
+    pub fn from_f32(x: f32, y: f32, policy: Conversion) -> Result<Self, OutOfRange> {
+        Ok(Self {
+            x: to_fixed(x, Axis::X, policy)?,
+            y: to_fixed(y, Axis::Y, policy)?,
+        })
+    }
+    /// First coordinate that does not fit into `I7F25`, regardless of policy.
+    /// Use this to report inputs that `Saturate` or `Wrap` would alter.
+    pub fn out_of_range(x: f32, y: f32) -> Option<OutOfRange> {
+        to_fixed(x, Axis::X, Conversion::Error)
+            .and(to_fixed(y, Axis::Y, Conversion::Error))
+            .err()
+    }
+}
+impl core::fmt::Display for Point {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+impl From<In> for Point {
+    fn from(In([x, y]): In) -> Self {
+        Self { x, y }
+    }
+}
+impl From<Point> for In {
+    fn from(p: Point) -> Self {
+        In([p.x, p.y])
+    }
+}
+impl Data for Point {
+    const SIZE: usize = <In as Data>::SIZE;
+    fn from_le_bytes(b: &[u8]) -> FpgaApiResult<Self> {
+        In::from_le_bytes(b).map(Self::from)
+    }
+    fn from_be_bytes(b: &[u8]) -> FpgaApiResult<Self> {
+        In::from_be_bytes(b).map(Self::from)
+    }
+    fn write_le_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        In::from(self).write_le_bytes(b)
+    }
+    fn write_be_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        In::from(self).write_be_bytes(b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICIES: [Conversion; 3] = [Conversion::Error, Conversion::Saturate, Conversion::Wrap];
+
+    #[test]
+    fn in_range_values_convert_exactly_under_every_policy() {
+        for policy in POLICIES {
+            for &v in &[0.0, 1.5, -1.5, -64.0, 63.75] {
+                assert_eq!(to_fixed(v, Axis::X, policy), Ok(I7F25::from_num(v)));
+            }
+        }
+    }
+
+    #[test]
+    fn nan_is_rejected_under_every_policy() {
+        for policy in POLICIES {
+            let err = to_fixed(f32::NAN, Axis::Y, policy).unwrap_err();
+            assert_eq!(err.axis, Axis::Y);
+            assert!(err.value.is_nan());
+        }
+    }
+
+    #[test]
+    fn error_policy_rejects_out_of_range() {
+        for &v in &[64.0, -64.5, 1e9, f32::INFINITY, f32::NEG_INFINITY] {
+            assert_eq!(
+                to_fixed(v, Axis::X, Conversion::Error),
+                Err(OutOfRange {
+                    axis: Axis::X,
+                    value: v
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn saturate_policy_clamps_to_range() {
+        let sat = |v| to_fixed(v, Axis::X, Conversion::Saturate);
+        assert_eq!(sat(64.0), Ok(I7F25::MAX));
+        assert_eq!(sat(1e9), Ok(I7F25::MAX));
+        assert_eq!(sat(f32::INFINITY), Ok(I7F25::MAX));
+        assert_eq!(sat(-64.5), Ok(I7F25::MIN));
+        assert_eq!(sat(f32::NEG_INFINITY), Ok(I7F25::MIN));
+    }
+
+    #[test]
+    fn wrap_policy_wraps_finite_values_and_rejects_infinities() {
+        let wrap = |v| to_fixed(v, Axis::X, Conversion::Wrap);
+        assert_eq!(wrap(64.0), Ok(I7F25::from_num(-64)));
+        assert_eq!(wrap(65.5), Ok(I7F25::from_num(-62.5)));
+        assert_eq!(wrap(-65.0), Ok(I7F25::from_num(63)));
+        assert!(wrap(f32::INFINITY).is_err());
+        assert!(wrap(f32::NEG_INFINITY).is_err());
+    }
+
+    #[test]
+    fn from_f32_reports_the_offending_axis() {
+        assert_eq!(
+            Point::from_f32(1.0, -2.0, Conversion::Error),
+            Ok(Point::new(I7F25::from_num(1), I7F25::from_num(-2)))
+        );
+        assert_eq!(
+            Point::from_f32(1.0, 100.0, Conversion::Error)
+                .unwrap_err()
+                .axis,
+            Axis::Y
+        );
+        assert_eq!(
+            Point::from_f32(1.0, 100.0, Conversion::Saturate),
+            Ok(Point::new(I7F25::from_num(1), I7F25::MAX))
+        );
+    }
+
+    #[test]
+    fn out_of_range_ignores_policy() {
+        assert_eq!(Point::out_of_range(1.0, -2.0), None);
+        assert_eq!(
+            Point::out_of_range(1.0, 100.0),
+            Some(OutOfRange {
+                axis: Axis::Y,
+                value: 100.0
+            })
+        );
+        // The first offending coordinate is reported.
+        assert_eq!(Point::out_of_range(-100.0, 100.0).unwrap().axis, Axis::X);
+        assert_eq!(
+            Point::out_of_range(f32::NAN, 0.0).map(|e| e.axis),
+            Some(Axis::X)
+        );
+    }
+
+    #[test]
+    fn point_is_laid_out_like_the_generated_input() {
+        let p = Point::new(I7F25::from_num(1), I7F25::from_bits(-1));
+        let mut bytes = [0; 8];
+        p.write_le_bytes(&mut bytes).unwrap();
+        assert_eq!(bytes, [0, 0, 0, 2, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(<Point as Data>::from_le_bytes(&bytes).unwrap(), p);
+        assert_eq!(In::from_le_bytes(&bytes).unwrap(), In([p.x, p.y]));
+    }
+}
@@ -0,0 +1,252 @@
+//! Wait for a readable resource to reach a condition (e.g. busy/done bits).
+
+use std::time::{Duration, Instant};
+
+use crate::traits::{Readable, Session};
+use crate::FpgaApiError;
+
+/// How long to sleep between successive reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    /// Read again immediately.
+    Spin,
+    /// Sleep a fixed time between reads.
+    Fixed(Duration),
+    /// Start at `initial` and double after every read, up to `max`.
+    Exponential { initial: Duration, max: Duration },
+}
+impl Backoff {
+    /// Delay before read number `attempt` (starting at 1 for the second read).
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        match *self {
+            Backoff::Spin => Duration::from_secs(0),
+            Backoff::Fixed(d) => d,
+            Backoff::Exponential { initial, max } => initial
+                .checked_mul(1 << (attempt - 1).min(31))
+                .map_or(max, |d| d.min(max)),
+        }
+    }
+}
+
+/// Error from `wait_until`.
+#[derive(Debug)]
+pub enum PollError<V> {
+    /// The condition did not hold before the timeout.
+    Timeout {
+        /// Last value read from the resource.
+        last: V,
+        /// Time spent polling.
+        elapsed: Duration,
+        /// Number of reads performed.
+        reads: u32,
+    },
+    /// Reading the resource failed.
+    Session(FpgaApiError),
+}
+impl<V: std::fmt::Debug> std::fmt::Display for PollError<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PollError::Timeout {
+                last,
+                elapsed,
+                reads,
+            } => write!(
+                f,
+                "Timed out after {:?} and {} reads, last value {:?}",
+                elapsed, reads, last
+            ),
+            PollError::Session(e) => write!(f, "Polling failed: {}", e),
+        }
+    }
+}
+impl<V: std::fmt::Debug> std::error::Error for PollError<V> {}
+
+/// What to do after a read.
+pub(crate) enum Step<V> {
+    /// The condition holds for the value.
+    Done(V),
+    /// Read again after the given delay (zero means right away).
+    Retry(Duration),
+    /// Give up.
+    TimedOut(PollError<V>),
+}
+
+/// Timeout and backoff bookkeeping shared by the blocking and async
+/// `wait_until`, which only differ in how they read and sleep.
+pub(crate) struct Poller {
+    start: Instant,
+    timeout: Duration,
+    backoff: Backoff,
+    reads: u32,
+}
+impl Poller {
+    pub(crate) fn new(timeout: Duration, backoff: Backoff) -> Self {
+        Self {
+            start: Instant::now(),
+            timeout,
+            backoff,
+            reads: 0,
+        }
+    }
+    /// Decide on the next step after reading `val`, for which the condition
+    /// holds if `done`.
+    pub(crate) fn step<V>(&mut self, val: V, done: bool) -> Step<V> {
+        self.reads += 1;
+        if done {
+            return Step::Done(val);
+        }
+        let elapsed = self.start.elapsed();
+        if elapsed >= self.timeout {
+            return Step::TimedOut(PollError::Timeout {
+                last: val,
+                elapsed,
+                reads: self.reads,
+            });
+        }
+        Step::Retry(self.backoff.delay(self.reads).min(self.timeout - elapsed))
+    }
+}
+
+/// Read `resource` until `done` holds for its value and return that value.
+/// The resource is always read at least once, even with a zero timeout.
+pub fn wait_until<S, R, F>(
+    sesh: &S,
+    resource: &R,
+    mut done: F,
+    timeout: Duration,
+    backoff: Backoff,
+) -> Result<R::Value, PollError<R::Value>>
+where
+    S: Session,
+    R: Readable,
+    F: FnMut(&R::Value) -> bool,
+{
+    let mut poller = Poller::new(timeout, backoff);
+    loop {
+        let val = sesh.read(resource).map_err(PollError::Session)?;
+        let done = done(&val);
+        match poller.step(val, done) {
+            Step::Done(val) => return Ok(val),
+            Step::TimedOut(e) => return Err(e),
+            Step::Retry(delay) if delay > Duration::from_secs(0) => std::thread::sleep(delay),
+            Step::Retry(_) => std::hint::spin_loop(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    use crate::resources::Resource;
+    use crate::session::read_bytes;
+    use crate::traits::{ReadOnly, Writable};
+    use crate::FpgaApiResult;
+
+    /// Register block whose 32-bit word counts the reads made so far, like a
+    /// status register that changes while it is polled.
+    struct Ramp {
+        reads: Cell<u32>,
+    }
+    impl Session for Ramp {
+        fn read<R: Readable>(&self, resource: &R) -> FpgaApiResult<R::Value> {
+            self.reads.set(self.reads.get() + 1);
+            read_bytes(&self.reads.get().to_le_bytes(), resource)
+        }
+        fn write<R: Writable>(&mut self, _: &R, _: R::Value) -> FpgaApiResult<()> {
+            Err(FpgaApiError::from("Read only!"))
+        }
+    }
+    impl Drop for Ramp {
+        fn drop(&mut self) {}
+    }
+
+    fn status() -> Resource<u32, ReadOnly> {
+        Resource::new("status", 0)
+    }
+
+    fn ramp() -> Ramp {
+        Ramp {
+            reads: Cell::new(0),
+        }
+    }
+
+    #[test]
+    fn returns_once_the_register_changes() {
+        let sesh = ramp();
+        let val = wait_until(
+            &sesh,
+            &status(),
+            |&v| v >= 3,
+            Duration::from_secs(5),
+            Backoff::Spin,
+        )
+        .unwrap();
+        assert_eq!(val, 3);
+        assert_eq!(sesh.reads.get(), 3);
+    }
+
+    #[test]
+    fn timeout_carries_last_value_and_read_count() {
+        let sesh = ramp();
+        let timeout = Duration::from_millis(20);
+        match wait_until(
+            &sesh,
+            &status(),
+            |_| false,
+            timeout,
+            Backoff::Fixed(Duration::from_millis(5)),
+        ) {
+            Err(PollError::Timeout {
+                last,
+                elapsed,
+                reads,
+            }) => {
+                assert_eq!(reads, sesh.reads.get());
+                assert_eq!(last, reads);
+                assert!(reads >= 2);
+                assert!(elapsed >= timeout);
+            }
+            other => panic!("Expected a timeout, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn zero_timeout_reads_once() {
+        let sesh = ramp();
+        let res = wait_until(
+            &sesh,
+            &status(),
+            |_| false,
+            Duration::from_secs(0),
+            Backoff::Spin,
+        );
+        assert!(matches!(
+            res,
+            Err(PollError::Timeout {
+                last: 1,
+                reads: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn exponential_backoff_doubles_up_to_its_cap() {
+        let ms = Duration::from_millis;
+        let backoff = Backoff::Exponential {
+            initial: ms(1),
+            max: ms(10),
+        };
+        let delays: Vec<_> = (1..=6).map(|a| backoff.delay(a)).collect();
+        assert_eq!(delays, [ms(1), ms(2), ms(4), ms(8), ms(10), ms(10)]);
+        // Overflowing the multiplication also yields the cap.
+        assert_eq!(backoff.delay(u32::MAX), ms(10));
+        let huge = Backoff::Exponential {
+            initial: Duration::MAX,
+            max: ms(10),
+        };
+        assert_eq!(huge.delay(2), ms(10));
+    }
+}
@@ -0,0 +1,179 @@
+//! Named FPGA address regions, each handing out its session at most once.
+//!
+//! A bitstream can host several cores behind the HPS-to-FPGA bridges. Each
+//! core gets its own `RegionCell` so that different subsystems of a process
+//! can own different cores, while any single core is still only owned once.
+//! Sessions are taken through the `fpga_manager::Loaded` token, so a region
+//! is never opened before the FPGA has been programmed.
+
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(feature = "std")]
+use crate::session::MmapSesh;
+#[cfg(feature = "std")]
+use crate::{FpgaApiError, FpgaApiResult};
+
+/// Base address of the (full) HPS-to-FPGA bridge on the Cyclone V.
+pub const H2F_BRIDGE_BASE: u64 = 0xC000_0000;
+/// Base address of the lightweight HPS-to-FPGA bridge on the Cyclone V.
+pub const LW_H2F_BRIDGE_BASE: u64 = 0xFF20_0000;
+
+/// Physical address region of an FPGA core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub name: &'static str,
+    pub base: u64,
+    pub span: usize,
+}
+impl Region {
+    pub const fn new(name: &'static str, base: u64, span: usize) -> Self {
+        Self { name, base, span }
+    }
+    /// Address of the last byte in the region.
+    pub const fn end(&self) -> u64 {
+        self.base + self.span as u64 - 1
+    }
+}
+
+/// Owner of a region that opens its session of type `S` at most once per
+/// process. Meant to be declared as a `static`.
+#[cfg(feature = "std")]
+pub struct RegionCell<S> {
+    region: Region,
+    open: fn(&Region) -> FpgaApiResult<S>,
+    taken: AtomicBool,
+}
+#[cfg(feature = "std")]
+impl<S> RegionCell<S> {
+    pub const fn new(region: Region, open: fn(&Region) -> FpgaApiResult<S>) -> Self {
+        Self {
+            region,
+            open,
+            taken: AtomicBool::new(false),
+        }
+    }
+    /// The region this cell owns.
+    pub fn region(&self) -> &Region {
+        &self.region
+    }
+    /// Whether the session has been handed out.
+    pub fn is_taken(&self) -> bool {
+        self.taken.load(Ordering::SeqCst)
+    }
+    /// Open the region's session. Fails on every call after the first
+    /// successful one; a failed open may be retried. Public callers go
+    /// through `fpga_manager::Loaded::take`.
+    pub(crate) fn take(&self) -> FpgaApiResult<S> {
+        if self
+            .taken
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(FpgaApiError::from(
+                "It is forbidden to create more than one session for a region!",
+            ));
+        }
+        let sesh = (self.open)(&self.region);
+        if sesh.is_err() {
+            self.taken.store(false, Ordering::SeqCst);
+        }
+        sesh
+    }
+    /// Open the region's session and run `check` on it (e.g.
+    /// `Identity::verify`) before handing it out. If the check fails, the
+    /// session is dropped and may be taken again.
+    pub(crate) fn take_checked<F>(&self, check: F) -> FpgaApiResult<S>
+    where
+        F: FnOnce(&S) -> FpgaApiResult<()>,
+    {
+        let sesh = self.take()?;
+        match check(&sesh) {
+            Ok(()) => Ok(sesh),
+            Err(e) => {
+                drop(sesh);
+                self.taken.store(false, Ordering::SeqCst);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Map a region of physical memory through `/dev/mem` (requires root).
+#[cfg(feature = "std")]
+pub(crate) fn open_dev_mem(region: &Region) -> FpgaApiResult<MmapSesh> {
+    use memmap::MmapOptions;
+    use std::fs::OpenOptions;
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(false)
+        .open("/dev/mem")?;
+    // The mapping must start on a page boundary (the system ID core, for
+    // one, is not page aligned).
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+    let skip = region.base % page;
+    let mmap = unsafe {
+        MmapOptions::new()
+            .offset(region.base - skip)
+            .len(skip as usize + region.span)
+            .map_mut(&file)?
+    };
+    MmapSesh::with_offset(mmap, skip as usize)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    const REGION: Region = Region::new("test", H2F_BRIDGE_BASE, 16);
+
+    fn open(region: &Region) -> FpgaApiResult<u64> {
+        Ok(region.base)
+    }
+    fn fail(_: &Region) -> FpgaApiResult<u64> {
+        Err(FpgaApiError::from("open failed"))
+    }
+
+    #[test]
+    fn resources_must_fit_in_their_region() {
+        use crate::resources::Resource;
+        use crate::traits::ReadWrite;
+
+        assert!(Resource::<u32, ReadWrite>::in_region(&REGION, "last", 12).is_ok());
+        assert!(Resource::<u32, ReadWrite>::in_region(&REGION, "straddling", 14).is_err());
+        assert!(Resource::<u32, ReadWrite>::in_region(&REGION, "far", usize::MAX).is_err());
+    }
+
+    #[test]
+    fn second_take_fails() {
+        let cell = RegionCell::new(REGION, open);
+        assert_eq!(cell.take().unwrap(), H2F_BRIDGE_BASE);
+        assert!(cell.is_taken());
+        assert!(cell.take().is_err());
+        assert!(cell.take_checked(|_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn failed_open_clears_taken() {
+        let cell = RegionCell::new(REGION, fail);
+        for _ in 0..2 {
+            let e = cell.take().unwrap_err();
+            assert_eq!(e.to_string(), "open failed");
+            assert!(!cell.is_taken());
+        }
+    }
+
+    #[test]
+    fn failed_check_clears_taken() {
+        let cell = RegionCell::new(REGION, open);
+        let e = cell
+            .take_checked(|_| Err(FpgaApiError::from("wrong bitstream")))
+            .unwrap_err();
+        assert_eq!(e.to_string(), "wrong bitstream");
+        assert!(!cell.is_taken());
+        assert_eq!(cell.take_checked(|_| Ok(())).unwrap(), H2F_BRIDGE_BASE);
+        assert!(cell.is_taken());
+    }
+}
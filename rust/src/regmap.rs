@@ -0,0 +1,113 @@
+//! Declarative register maps.
+//!
+//! A `RegisterMap` describes the registers of a core (name, byte offset, size,
+//! and access) as plain `const` data, so the same definition can drive the
+//! Rust resources and generated hardware wrappers.
+
+use crate::{FpgaApiError, FpgaApiResult};
+
+/// Bytes per bus word.
+pub const WORD_SIZE: usize = 4;
+
+/// Bus access of a register, mirroring the `IOState` typestates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    ReadOnly,
+    ReadWrite,
+    WriteOnly,
+}
+impl Access {
+    pub fn readable(self) -> bool {
+        self != Access::WriteOnly
+    }
+    pub fn writable(self) -> bool {
+        self != Access::ReadOnly
+    }
+}
+
+/// A register of a register map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Register {
+    pub name: &'static str,
+    /// Byte offset, a multiple of `WORD_SIZE`.
+    pub offset: usize,
+    /// Size of the register's data in bytes.
+    pub size: usize,
+    pub access: Access,
+    /// Number of vector elements, one per bus word, or 1 for a scalar.
+    pub elements: usize,
+}
+impl Register {
+    pub const fn new(name: &'static str, offset: usize, size: usize, access: Access) -> Self {
+        Self {
+            name,
+            offset,
+            size,
+            access,
+            elements: 1,
+        }
+    }
+    /// Mark the register as a vector of `len` one-word elements (e.g. a
+    /// Clash `Vec`), with element 0 in the lowest word.
+    pub const fn vector(mut self, len: usize) -> Self {
+        self.elements = len;
+        self
+    }
+    /// Number of bus words the register spans.
+    pub const fn words(&self) -> usize {
+        self.size.div_ceil(WORD_SIZE)
+    }
+    /// Index of the register's first bus word.
+    pub const fn word_offset(&self) -> usize {
+        self.offset / WORD_SIZE
+    }
+}
+
+/// The registers of a core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterMap {
+    pub name: &'static str,
+    pub registers: &'static [Register],
+}
+impl RegisterMap {
+    pub const fn new(name: &'static str, registers: &'static [Register]) -> Self {
+        Self { name, registers }
+    }
+    /// Register with the given name.
+    pub fn register(&self, name: &str) -> Option<&Register> {
+        self.registers.iter().find(|r| r.name == name)
+    }
+    /// Number of bus words covered by the map.
+    pub fn words(&self) -> usize {
+        self.registers
+            .iter()
+            .map(|r| r.word_offset() + r.words())
+            .max()
+            .unwrap_or(0)
+    }
+    /// Check that registers are word aligned and do not overlap.
+    pub fn check(&self) -> FpgaApiResult<()> {
+        for (i, a) in self.registers.iter().enumerate() {
+            if a.offset % WORD_SIZE != 0 {
+                return Err(FpgaApiError::from("Register is not word aligned!"));
+            }
+            if a.size == 0 {
+                return Err(FpgaApiError::from("Register has no data!"));
+            }
+            if a.elements > 1 && a.size != a.elements * WORD_SIZE {
+                return Err(FpgaApiError::from(
+                    "Vector register elements must be one word each!",
+                ));
+            }
+            for b in &self.registers[i + 1..] {
+                let a_words = a.word_offset()..a.word_offset() + a.words();
+                if a_words.contains(&b.word_offset())
+                    || (b.word_offset()..b.word_offset() + b.words()).contains(&a.word_offset())
+                {
+                    return Err(FpgaApiError::from("Registers overlap!"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
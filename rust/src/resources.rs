@@ -1,46 +1,78 @@
 //! Implementation of FPGA resources.
 
-use crate::traits::{Data, IOState, ReadOnly, ReadWrite, Readable, Writable};
+use crate::region::Region;
+use crate::traits::{
+    ByteOrder, Data, IOState, LittleEndian, ReadOnly, ReadWrite, Readable, Writable, WriteOnly,
+};
+use crate::{FpgaApiError, FpgaApiResult};
 
-use std::marker::PhantomData;
+use core::marker::PhantomData;
 
-/// Representation of FPGA resource with associated data type and I/O state as
-/// part of the type.
-pub struct Resource<D: Data, I: IOState> {
+/// Representation of FPGA resource with associated data type, I/O state and
+/// byte order as part of the type.
+pub struct Resource<D: Data, I: IOState, E: ByteOrder = LittleEndian> {
     name: &'static str,
     offset: usize,
     _ty: PhantomData<D>,
     _st: PhantomData<I>,
+    _ord: PhantomData<E>,
 }
-impl<D: Data, I: IOState> Resource<D, I> {
-    pub fn new(name: &'static str, offset: usize) -> Self {
+impl<D: Data, I: IOState, E: ByteOrder> Resource<D, I, E> {
+    /// Resource `offset` bytes into `region`. Fails if it does not fit in the
+    /// region.
+    pub fn in_region(region: &Region, name: &'static str, offset: usize) -> FpgaApiResult<Self> {
+        match offset.checked_add(D::SIZE) {
+            Some(end) if end <= region.span => Ok(Self::new(name, offset)),
+            _ => Err(FpgaApiError::from("Resource lies outside of its region!")),
+        }
+    }
+    /// Unchecked constructor for the crate's own register definitions.
+    pub(crate) fn new(name: &'static str, offset: usize) -> Self {
         Self {
             name,
             offset,
             _ty: PhantomData,
             _st: PhantomData,
+            _ord: PhantomData,
         }
     }
 }
-impl<D: Data, I: IOState> std::fmt::Display for Resource<D, I> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+// Not derived, since that would require the phantom types to be `Clone`.
+impl<D: Data, I: IOState, E: ByteOrder> Clone for Resource<D, I, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<D: Data, I: IOState, E: ByteOrder> Copy for Resource<D, I, E> {}
+impl<D: Data, I: IOState, E: ByteOrder> core::fmt::Display for Resource<D, I, E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{} at byte offset {}", self.name, self.offset)
     }
 }
-impl<D: Data> Readable for Resource<D, ReadOnly> {
+impl<D: Data, E: ByteOrder> Readable for Resource<D, ReadOnly, E> {
+    type Value = D;
+    type Order = E;
+    fn byte_offset(&self) -> usize {
+        self.offset
+    }
+}
+impl<D: Data, E: ByteOrder> Readable for Resource<D, ReadWrite, E> {
     type Value = D;
+    type Order = E;
     fn byte_offset(&self) -> usize {
         self.offset
     }
 }
-impl<D: Data> Readable for Resource<D, ReadWrite> {
+impl<D: Data, E: ByteOrder> Writable for Resource<D, ReadWrite, E> {
     type Value = D;
+    type Order = E;
     fn byte_offset(&self) -> usize {
         self.offset
     }
 }
-impl<D: Data> Writable for Resource<D, ReadWrite> {
+impl<D: Data, E: ByteOrder> Writable for Resource<D, WriteOnly, E> {
     type Value = D;
+    type Order = E;
     fn byte_offset(&self) -> usize {
         self.offset
     }
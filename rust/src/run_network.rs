@@ -0,0 +1,74 @@
+//! Register map of Clash entity `runNetwork`, generated from its manifest and
+//! the signature `topEntity :: Vec 2 (SFixed 7 25) -> Vec 1 (SFixed 7 25)`.
+//! DO NOT MODIFY.
+
+#![allow(dead_code)]
+
+use crate::regmap::{Access, Register, RegisterMap};
+use crate::resources::Resource;
+use crate::traits::{Data, ReadOnly, ReadWrite};
+use crate::{FpgaApiError, FpgaApiResult};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct In(pub [fixed::types::I7F25; 2]);
+impl Data for In {
+    const SIZE: usize = 2 * <fixed::types::I7F25 as Data>::SIZE;
+    fn from_le_bytes(b: &[u8]) -> FpgaApiResult<Self> {
+        if b.len() != Self::SIZE {
+            return Err(FpgaApiError::from("Wrong number of bytes!"));
+        }
+        let mut v = [<fixed::types::I7F25>::default(); 2];
+        for (v, ch) in v.iter_mut().zip(b.chunks_exact(<fixed::types::I7F25 as Data>::SIZE)) {
+            *v = <fixed::types::I7F25 as Data>::from_le_bytes(ch)?;
+        }
+        Ok(Self(v))
+    }
+    fn from_be_bytes(b: &[u8]) -> FpgaApiResult<Self> {
+        if b.len() != Self::SIZE {
+            return Err(FpgaApiError::from("Wrong number of bytes!"));
+        }
+        let mut v = [<fixed::types::I7F25>::default(); 2];
+        for (v, ch) in v.iter_mut().zip(b.chunks_exact(<fixed::types::I7F25 as Data>::SIZE)) {
+            *v = <fixed::types::I7F25 as Data>::from_be_bytes(ch)?;
+        }
+        Ok(Self(v))
+    }
+    fn write_le_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            return Err(FpgaApiError::from("Wrong number of bytes!"));
+        }
+        for (v, ch) in self.0.iter().zip(b.chunks_exact_mut(<fixed::types::I7F25 as Data>::SIZE)) {
+            <fixed::types::I7F25 as Data>::write_le_bytes(*v, ch)?;
+        }
+        Ok(())
+    }
+    fn write_be_bytes(self, b: &mut [u8]) -> FpgaApiResult<()> {
+        if b.len() != Self::SIZE {
+            return Err(FpgaApiError::from("Wrong number of bytes!"));
+        }
+        for (v, ch) in self.0.iter().zip(b.chunks_exact_mut(<fixed::types::I7F25 as Data>::SIZE)) {
+            <fixed::types::I7F25 as Data>::write_be_bytes(*v, ch)?;
+        }
+        Ok(())
+    }
+}
+
+pub const IN_OFFSET: usize = 0;
+/// Port `in` of `runNetwork`.
+pub fn in_register() -> Resource<In, ReadWrite> {
+    Resource::new("in", IN_OFFSET)
+}
+
+pub const OUT_OFFSET: usize = 8;
+/// Port `out` of `runNetwork`.
+pub fn out_register() -> Resource<fixed::types::I7F25, ReadOnly> {
+    Resource::new("out", OUT_OFFSET)
+}
+
+pub const RUN_NETWORK_REGISTER_MAP: RegisterMap = RegisterMap::new(
+    "runNetwork",
+    &[
+        Register::new("in", IN_OFFSET, <In as Data>::SIZE, Access::ReadWrite).vector(2),
+        Register::new("out", OUT_OFFSET, <fixed::types::I7F25 as Data>::SIZE, Access::ReadOnly),
+    ],
+);
@@ -0,0 +1,333 @@
+//! Declarative safe-state sequences run at session start and teardown.
+//!
+//! A `Sequence` is an ordered list of register writes and verification reads.
+//! `SafeSesh` runs a startup sequence when it is created and a teardown
+//! sequence when it is shut down or dropped, including while a panic unwinds
+//! (but not with `panic = "abort"`).
+//!
+//! Sessions such as `MmapSesh` and `UioSesh` only move bytes and know nothing
+//! about the core behind them, so they neither initialize nor quiesce it.
+//! Wrap them in a `SafeSesh` with the core's sequences instead.
+
+use std::fmt::{self, Debug};
+
+use crate::traits::{Readable, Session, Writable};
+use crate::{FpgaApiError, FpgaApiResult};
+
+type Step<S> = Box<dyn Fn(&mut S) -> FpgaApiResult<()> + Send>;
+
+/// Verification read that did not return the expected value.
+#[derive(Debug)]
+pub struct VerifyError {
+    pub expected: String,
+    pub actual: String,
+}
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Verification failed: expected {}, read {}",
+            self.expected, self.actual
+        )
+    }
+}
+impl std::error::Error for VerifyError {}
+
+/// Failures of a sequence, by step index.
+#[derive(Debug)]
+pub struct SequenceError {
+    pub failures: Vec<(usize, FpgaApiError)>,
+}
+impl fmt::Display for SequenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Safe-state sequence failed at")?;
+        for (i, (step, e)) in self.failures.iter().enumerate() {
+            let sep = if i == 0 { "" } else { ";" };
+            write!(f, "{} step {} ({})", sep, step, e)?;
+        }
+        Ok(())
+    }
+}
+impl std::error::Error for SequenceError {}
+
+/// Ordered list of register writes and verification reads.
+pub struct Sequence<S: Session> {
+    steps: Vec<Step<S>>,
+}
+impl<S: Session> Sequence<S> {
+    pub fn new() -> Self {
+        Self { steps: Vec::new() }
+    }
+    /// Append a write of `val` to `resource`.
+    pub fn write<R>(mut self, resource: &R, val: R::Value) -> Self
+    where
+        R: Writable + Clone + Send + 'static,
+        R::Value: Clone + Send + 'static,
+    {
+        let resource = resource.clone();
+        self.steps.push(Box::new(move |sesh: &mut S| {
+            sesh.write(&resource, val.clone())
+        }));
+        self
+    }
+    /// Append a read of `resource` that must return `expected`.
+    pub fn verify<R>(mut self, resource: &R, expected: R::Value) -> Self
+    where
+        R: Readable + Clone + Send + 'static,
+        R::Value: PartialEq + Debug + Send + 'static,
+    {
+        let resource = resource.clone();
+        self.steps.push(Box::new(move |sesh: &mut S| {
+            let actual = sesh.read(&resource)?;
+            if actual == expected {
+                Ok(())
+            } else {
+                Err(VerifyError {
+                    expected: format!("{:?}", expected),
+                    actual: format!("{:?}", actual),
+                }
+                .into())
+            }
+        }));
+        self
+    }
+    /// Run the steps in order, stopping at the first failure.
+    pub fn run(&self, sesh: &mut S) -> FpgaApiResult<()> {
+        for (i, step) in self.steps.iter().enumerate() {
+            if let Err(e) = step(sesh) {
+                return Err(SequenceError {
+                    failures: vec![(i, e)],
+                }
+                .into());
+            }
+        }
+        Ok(())
+    }
+    /// Run every step even if earlier ones fail, so as much of the safe
+    /// state as possible is applied.
+    pub fn run_all(&self, sesh: &mut S) -> FpgaApiResult<()> {
+        let failures: Vec<_> = self
+            .steps
+            .iter()
+            .enumerate()
+            .filter_map(|(i, step)| step(sesh).err().map(|e| (i, e)))
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(SequenceError { failures }.into())
+        }
+    }
+}
+impl<S: Session> Default for Sequence<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Receives errors that happen on drop, where there is no caller to return
+/// them to.
+pub type Reporter = Box<dyn FnMut(&FpgaApiError) + Send>;
+
+/// Default reporter, printing to stderr.
+pub fn report_to_stderr(e: &FpgaApiError) {
+    eprintln!("ERROR during FPGA session teardown: {}", e);
+}
+
+/// Session that enforces a startup sequence on creation and a teardown
+/// sequence on shutdown or drop.
+pub struct SafeSesh<S: Session> {
+    sesh: S,
+    teardown: Sequence<S>,
+    report: Reporter,
+    torn_down: bool,
+}
+impl<S: Session> SafeSesh<S> {
+    /// Run `startup` on `sesh`. If it fails, `teardown` is applied and the
+    /// startup error is returned. Teardown failures on drop (including the
+    /// one after a failed startup) go to `report`, e.g. `report_to_stderr`.
+    pub fn new<F>(
+        sesh: S,
+        startup: &Sequence<S>,
+        teardown: Sequence<S>,
+        report: F,
+    ) -> FpgaApiResult<Self>
+    where
+        F: FnMut(&FpgaApiError) + Send + 'static,
+    {
+        let mut safe = Self {
+            sesh,
+            teardown,
+            report: Box::new(report),
+            torn_down: false,
+        };
+        // On failure, dropping `safe` applies the teardown sequence.
+        startup.run(&mut safe.sesh)?;
+        Ok(safe)
+    }
+    /// Run the teardown sequence now and return its result.
+    pub fn shutdown(mut self) -> FpgaApiResult<()> {
+        self.torn_down = true;
+        self.teardown.run_all(&mut self.sesh)
+    }
+}
+impl<S: Session> Session for SafeSesh<S> {
+    fn read<R: Readable>(&self, resource: &R) -> FpgaApiResult<R::Value> {
+        self.sesh.read(resource)
+    }
+    fn write<R: Writable>(&mut self, resource: &R, val: R::Value) -> FpgaApiResult<()> {
+        self.sesh.write(resource, val)
+    }
+}
+impl<S: Session> Drop for SafeSesh<S> {
+    fn drop(&mut self) {
+        if !self.torn_down {
+            self.torn_down = true;
+            if let Err(e) = self.teardown.run_all(&mut self.sesh) {
+                (self.report)(&e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::{Arc, Mutex};
+
+    use crate::resources::Resource;
+    use crate::session::SimSesh;
+    use crate::traits::ReadWrite;
+
+    /// Simulated register block that outlives the session using it.
+    #[derive(Clone)]
+    struct Probe(Arc<Mutex<SimSesh>>);
+    impl Probe {
+        fn new() -> Self {
+            Self(Arc::new(Mutex::new(SimSesh::new(8))))
+        }
+        fn get(&self, resource: &Resource<u32, ReadWrite>) -> u32 {
+            self.0.lock().unwrap().read(resource).unwrap()
+        }
+    }
+    impl Session for Probe {
+        fn read<R: Readable>(&self, resource: &R) -> FpgaApiResult<R::Value> {
+            self.0.lock().unwrap().read(resource)
+        }
+        fn write<R: Writable>(&mut self, resource: &R, val: R::Value) -> FpgaApiResult<()> {
+            self.0.lock().unwrap().write(resource, val)
+        }
+    }
+    impl Drop for Probe {
+        fn drop(&mut self) {}
+    }
+
+    fn enable() -> Resource<u32, ReadWrite> {
+        Resource::new("enable", 0)
+    }
+    fn output() -> Resource<u32, ReadWrite> {
+        Resource::new("output", 4)
+    }
+    fn outside() -> Resource<u32, ReadWrite> {
+        Resource::new("outside", 8)
+    }
+
+    fn startup() -> Sequence<Probe> {
+        Sequence::new().write(&enable(), 1).write(&output(), 7)
+    }
+    fn teardown() -> Sequence<Probe> {
+        Sequence::new().write(&output(), 0).write(&enable(), 0)
+    }
+
+    /// Reporter that collects the reported errors.
+    fn reports() -> (
+        Arc<Mutex<Vec<String>>>,
+        impl FnMut(&FpgaApiError) + Send + 'static,
+    ) {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = reports.clone();
+        (reports, move |e: &FpgaApiError| {
+            sink.lock().unwrap().push(e.to_string())
+        })
+    }
+
+    #[test]
+    fn teardown_runs_on_drop() {
+        let probe = Probe::new();
+        let (reports, report) = reports();
+        let safe = SafeSesh::new(probe.clone(), &startup(), teardown(), report).unwrap();
+        assert_eq!((probe.get(&enable()), probe.get(&output())), (1, 7));
+        drop(safe);
+        assert_eq!((probe.get(&enable()), probe.get(&output())), (0, 0));
+        assert!(reports.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn teardown_runs_when_a_panic_unwinds() {
+        let probe = Probe::new();
+        let sesh = probe.clone();
+        let unwound = panic::catch_unwind(AssertUnwindSafe(move || {
+            let mut safe = SafeSesh::new(sesh, &startup(), teardown(), report_to_stderr).unwrap();
+            safe.write(&output(), 9).unwrap();
+            panic!("panic while the core is running");
+        }));
+        assert!(unwound.is_err());
+        assert_eq!((probe.get(&enable()), probe.get(&output())), (0, 0));
+    }
+
+    #[test]
+    fn shutdown_returns_teardown_errors_and_skips_drop() {
+        let (reports, report) = reports();
+        let teardown = teardown().write(&outside(), 0);
+        let safe = SafeSesh::new(Probe::new(), &startup(), teardown, report).unwrap();
+        assert!(safe.shutdown().is_err());
+        assert!(reports.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn run_all_continues_after_a_failing_step() {
+        let probe = Probe::new();
+        let seq = Sequence::new()
+            .write(&outside(), 1)
+            .write(&output(), 2)
+            .write(&outside(), 3);
+        let e = seq.run_all(&mut probe.clone()).unwrap_err();
+        let e = e.downcast_ref::<SequenceError>().unwrap();
+        let steps: Vec<_> = e.failures.iter().map(|(i, _)| *i).collect();
+        assert_eq!(steps, [0, 2]);
+        assert_eq!(probe.get(&output()), 2);
+    }
+
+    #[test]
+    fn run_stops_at_the_first_failing_step() {
+        let probe = Probe::new();
+        let seq = Sequence::new().write(&outside(), 1).write(&output(), 2);
+        assert!(seq.run(&mut probe.clone()).is_err());
+        assert_eq!(probe.get(&output()), 0);
+    }
+
+    #[test]
+    fn verify_mismatch_is_reported() {
+        let (reports, report) = reports();
+        let teardown = teardown().verify(&output(), 1);
+        drop(SafeSesh::new(Probe::new(), &startup(), teardown, report).unwrap());
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].contains("step 2 (Verification failed: expected 1, read 0)"));
+    }
+
+    #[test]
+    fn failed_startup_still_runs_teardown() {
+        let probe = Probe::new();
+        let (reports, report) = reports();
+        let startup = startup().verify(&enable(), 2);
+        let e = SafeSesh::new(probe.clone(), &startup, teardown(), report)
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("expected 2, read 1"));
+        assert_eq!((probe.get(&enable()), probe.get(&output())), (0, 0));
+        assert!(reports.lock().unwrap().is_empty());
+    }
+}
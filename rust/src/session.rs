@@ -1,43 +1,184 @@
 //! Implementation of FPGA Session API (here for memory-mapped file API).
 
-use crate::traits::{Data, Readable, Session, Writable};
-use crate::FpgaApiResult;
+use core::ops::Range;
 
+use crate::traits::{ByteOrder, Data, Readable, Writable};
+use crate::{FpgaApiError, FpgaApiResult};
+
+#[cfg(feature = "alloc")]
+use crate::traits::Session;
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "std")]
 use memmap::MmapMut;
 
+/// Byte range of a resource inside a register block of `len` bytes, checked
+/// against the wire size of the resource's data type.
+fn byte_range<D: Data>(offset: usize, size: usize, len: usize) -> FpgaApiResult<Range<usize>> {
+    if size != D::SIZE {
+        return Err(FpgaApiError::from(
+            "Resource size does not match its data size!",
+        ));
+    }
+    match offset.checked_add(size) {
+        Some(stop) if stop <= len => Ok(offset..stop),
+        _ => Err(FpgaApiError::from(
+            "Resource lies outside of the register block!",
+        )),
+    }
+}
+
+/// Read a readable resource out of a raw register block in the resource's
+/// byte order.
+pub fn read_bytes<R: Readable>(mem: &[u8], resource: &R) -> FpgaApiResult<R::Value> {
+    let range =
+        byte_range::<R::Value>(resource.byte_offset(), resource.size_in_bytes(), mem.len())?;
+    R::Order::decode(&mem[range])
+}
+
+/// Validate and write a writable resource into a raw register block in the
+/// resource's byte order.
+pub fn write_bytes<R: Writable>(mem: &mut [u8], resource: &R, val: R::Value) -> FpgaApiResult<()> {
+    resource.validate(&val)?;
+    let range =
+        byte_range::<R::Value>(resource.byte_offset(), resource.size_in_bytes(), mem.len())?;
+    R::Order::encode(val, &mut mem[range])
+}
+
 /// Session for FPGA I/O through a memory-mapped file.
+#[cfg(feature = "std")]
 pub struct MmapSesh {
     mmap: MmapMut,
+    // Byte offset of the register block inside the mapping.
+    offset: usize,
 }
+#[cfg(feature = "std")]
 impl MmapSesh {
-    pub fn new(mmap: MmapMut) -> FpgaApiResult<Self> {
-        let mut sesh = Self { mmap };
-        sesh.initialize()?;
-        Ok(sesh)
-    }
-    /// Enforce critical FPGA/HW invariants for "initial" state.
-    pub fn initialize(&mut self) -> FpgaApiResult<()> {
-        // -- snip --
-        Ok(())
+    /// Session for the register block starting `offset` bytes into `mmap`,
+    /// e.g. when the block is not page aligned. Sessions are opened through
+    /// a region's `RegionCell`.
+    pub(crate) fn with_offset(mmap: MmapMut, offset: usize) -> FpgaApiResult<Self> {
+        if offset > mmap.len() {
+            return Err(FpgaApiError::from(
+                "Register block starts outside of the mapping!",
+            ));
+        }
+        Ok(Self { mmap, offset })
     }
 }
+#[cfg(feature = "std")]
 impl Session for MmapSesh {
     fn read<R: Readable>(&self, resource: &R) -> FpgaApiResult<R::Value> {
-        let start = resource.byte_offset();
-        let stop = start + resource.size_in_bytes();
-        let slc = &self.mmap[start..stop];
-        R::Value::from_le_bytes(slc)
+        read_bytes(&self.mmap[self.offset..], resource)
     }
     fn write<R: Writable>(&mut self, resource: &R, val: R::Value) -> FpgaApiResult<()> {
-        let start = resource.byte_offset();
-        let stop = start + resource.size_in_bytes();
-        self.mmap[start..stop].copy_from_slice(val.to_le_bytes().as_slice());
-        Ok(())
+        write_bytes(&mut self.mmap[self.offset..], resource, val)
     }
 }
+#[cfg(feature = "std")]
 impl Drop for MmapSesh {
+    fn drop(&mut self) {}
+}
+
+/// Session backed by plain memory, simulating a register block without
+/// hardware.
+#[cfg(feature = "alloc")]
+pub struct SimSesh {
+    mem: Vec<u8>,
+}
+#[cfg(feature = "alloc")]
+impl SimSesh {
+    /// Zero-initialized register block of `len` bytes.
+    pub fn new(len: usize) -> Self {
+        Self { mem: vec![0; len] }
+    }
+    /// Raw register block, e.g. to emulate hardware updating registers.
+    pub fn memory(&self) -> &[u8] {
+        &self.mem
+    }
+    /// Mutable raw register block.
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.mem
+    }
+}
+#[cfg(feature = "alloc")]
+impl Session for SimSesh {
+    fn read<R: Readable>(&self, resource: &R) -> FpgaApiResult<R::Value> {
+        read_bytes(&self.mem, resource)
+    }
+    fn write<R: Writable>(&mut self, resource: &R, val: R::Value) -> FpgaApiResult<()> {
+        write_bytes(&mut self.mem, resource, val)
+    }
+}
+#[cfg(feature = "alloc")]
+impl Drop for SimSesh {
     fn drop(&mut self) {
-        // Enforce critical FPGA/HW invariants for "final" or dropped state.
-        // -- snip --
+        // No hardware to put into a safe state.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::Resource;
+    use crate::traits::{LittleEndian, ReadWrite};
+
+    /// Resource that claims a size other than its data type's.
+    struct Misfit;
+    impl Readable for Misfit {
+        type Value = u32;
+        type Order = LittleEndian;
+        fn byte_offset(&self) -> usize {
+            0
+        }
+        fn size_in_bytes(&self) -> usize {
+            2
+        }
+    }
+    impl Writable for Misfit {
+        type Value = u32;
+        type Order = LittleEndian;
+        fn byte_offset(&self) -> usize {
+            0
+        }
+        fn size_in_bytes(&self) -> usize {
+            2
+        }
+    }
+
+    #[test]
+    fn size_mismatch_is_rejected() {
+        let mut mem = [0u8; 8];
+        assert!(read_bytes(&mem, &Misfit).is_err());
+        assert!(write_bytes(&mut mem, &Misfit, 7).is_err());
+        assert_eq!(mem, [0; 8]);
+    }
+
+    #[test]
+    fn resource_outside_of_block_is_rejected() {
+        let mut mem = [0u8; 8];
+        let inside = Resource::<u32, ReadWrite>::new("inside", 4);
+        let straddling = Resource::<u32, ReadWrite>::new("straddling", 6);
+        let overflowing = Resource::<u32, ReadWrite>::new("overflowing", usize::MAX - 1);
+        write_bytes(&mut mem, &inside, 0x0102_0304).unwrap();
+        assert_eq!(read_bytes(&mem, &inside).unwrap(), 0x0102_0304);
+        assert!(read_bytes(&mem, &straddling).is_err());
+        assert!(write_bytes(&mut mem, &straddling, 1).is_err());
+        assert!(read_bytes(&mem, &overflowing).is_err());
+        assert!(write_bytes(&mut mem, &overflowing, 1).is_err());
+        assert_eq!(mem, [0, 0, 0, 0, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn bool_only_decodes_zero_or_one() {
+        let flag = Resource::<bool, ReadWrite>::new("flag", 1);
+        let mut mem = [0u8, 0, 7];
+        assert!(!read_bytes(&mem, &flag).unwrap());
+        mem[1] = 1;
+        assert!(read_bytes(&mem, &flag).unwrap());
+        for invalid in [2, 0x80, 0xFF] {
+            mem[1] = invalid;
+            assert!(read_bytes(&mem, &flag).is_err());
+        }
     }
 }
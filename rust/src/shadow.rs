@@ -0,0 +1,192 @@
+//! Shadow copies of written register values.
+//!
+//! `ShadowSesh` remembers the bytes of every successful write, so write-only
+//! registers can be recalled and updated with read-modify-write, and slow
+//! registers need not be read back.
+
+use alloc::collections::BTreeMap;
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+use crate::traits::{ByteOrder, Data, ReadWritable, Readable, Session, Writable};
+use crate::{FpgaApiError, FpgaApiResult};
+
+/// Session adaptor that keeps a shadow copy of every value written.
+pub struct ShadowSesh<S: Session> {
+    sesh: S,
+    // Encoded bytes of the last write, by byte offset.
+    shadow: BTreeMap<usize, Vec<u8>>,
+}
+impl<S: Session> ShadowSesh<S> {
+    pub fn new(sesh: S) -> Self {
+        Self {
+            sesh,
+            shadow: BTreeMap::new(),
+        }
+    }
+    /// Value last written to `resource` through this session, if any.
+    pub fn last_written<R: Writable>(&self, resource: &R) -> FpgaApiResult<Option<R::Value>> {
+        match self.shadow.get(&resource.byte_offset()) {
+            Some(bytes) if bytes.len() == resource.size_in_bytes() => {
+                R::Order::decode(bytes).map(Some)
+            }
+            Some(_) => Err(FpgaApiError::from(
+                "Shadow value has a different size than the resource!",
+            )),
+            None => Ok(None),
+        }
+    }
+    /// Write `f(last)` to `resource`, where `last` is the value last written.
+    /// Fails if the register has not been written through this session yet.
+    pub fn modify<R, F>(&mut self, resource: &R, f: F) -> FpgaApiResult<()>
+    where
+        R: Writable,
+        F: FnOnce(R::Value) -> R::Value,
+    {
+        match self.last_written(resource)? {
+            Some(last) => self.write(resource, f(last)),
+            None => Err(FpgaApiError::from(
+                "Register has no shadow value to modify!",
+            )),
+        }
+    }
+    /// Value last written to `resource`, or a hardware read if there is none.
+    pub fn read_cached<R: ReadWritable>(
+        &self,
+        resource: &R,
+    ) -> FpgaApiResult<<R as Readable>::Value> {
+        match self.last_written(resource)? {
+            Some(val) => Ok(val),
+            None => self.sesh.read(resource),
+        }
+    }
+    /// Forget the shadow value of `resource`, e.g. after a hardware reset.
+    pub fn invalidate<R: Writable>(&mut self, resource: &R) {
+        self.shadow.remove(&resource.byte_offset());
+    }
+    /// Forget all shadow values.
+    pub fn clear(&mut self) {
+        self.shadow.clear();
+    }
+    /// Shadow state for diagnostics, printed as one hex line per register.
+    pub fn dump(&self) -> ShadowDump<'_> {
+        ShadowDump(&self.shadow)
+    }
+}
+impl<S: Session> Session for ShadowSesh<S> {
+    fn read<R: Readable>(&self, resource: &R) -> FpgaApiResult<R::Value> {
+        self.sesh.read(resource)
+    }
+    fn write<R: Writable>(&mut self, resource: &R, val: R::Value) -> FpgaApiResult<()> {
+        if resource.size_in_bytes() != R::Value::SIZE {
+            return Err(FpgaApiError::from(
+                "Resource size does not match its data size!",
+            ));
+        }
+        let mut bytes = vec![0; R::Value::SIZE];
+        // `Data` is not `Clone`; encode once and decode the copy to write.
+        R::Order::encode(val, &mut bytes)?;
+        self.sesh.write(resource, R::Order::decode(&bytes)?)?;
+        self.shadow.insert(resource.byte_offset(), bytes);
+        Ok(())
+    }
+}
+impl<S: Session> Drop for ShadowSesh<S> {
+    fn drop(&mut self) {
+        // The wrapped session puts the hardware into a safe state.
+    }
+}
+
+/// Diagnostic view of a `ShadowSesh`'s shadow state.
+pub struct ShadowDump<'a>(&'a BTreeMap<usize, Vec<u8>>);
+impl fmt::Display for ShadowDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (offset, bytes) in self.0 {
+            write!(f, "{:#06x}:", offset)?;
+            for b in bytes {
+                write!(f, " {:02x}", b)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    use crate::resources::Resource;
+    use crate::session::SimSesh;
+    use crate::traits::{BigEndian, ReadWrite, WriteOnly};
+    use crate::validate::{Bounds, Checked};
+
+    fn control() -> Resource<u32, WriteOnly> {
+        Resource::new("control", 0)
+    }
+    fn status() -> Resource<u16, ReadWrite, BigEndian> {
+        Resource::new("status", 4)
+    }
+
+    #[test]
+    fn last_written_recalls_values() {
+        let mut sesh = ShadowSesh::new(SimSesh::new(8));
+        assert_eq!(sesh.last_written(&control()).unwrap(), None);
+        sesh.write(&control(), 0x10).unwrap();
+        sesh.write(&control(), 0x11).unwrap();
+        sesh.write(&status(), 0x0102).unwrap();
+        assert_eq!(sesh.last_written(&control()).unwrap(), Some(0x11));
+        assert_eq!(sesh.last_written(&status()).unwrap(), Some(0x0102));
+        sesh.invalidate(&control());
+        assert_eq!(sesh.last_written(&control()).unwrap(), None);
+        sesh.clear();
+        assert_eq!(sesh.last_written(&status()).unwrap(), None);
+    }
+
+    #[test]
+    fn modify_updates_write_only_registers() {
+        let mut sesh = ShadowSesh::new(SimSesh::new(8));
+        assert!(sesh.modify(&control(), |v| v | 1).is_err());
+        sesh.write(&control(), 0b100).unwrap();
+        sesh.modify(&control(), |v| v | 1).unwrap();
+        assert_eq!(sesh.last_written(&control()).unwrap(), Some(0b101));
+        assert_eq!(sesh.sesh.memory()[..4], 0b101u32.to_le_bytes());
+    }
+
+    #[test]
+    fn read_cached_prefers_the_shadow() {
+        let mut sesh = ShadowSesh::new(SimSesh::new(8));
+        sesh.sesh.memory_mut()[4..6].copy_from_slice(&[0xAB, 0xCD]);
+        assert_eq!(sesh.read_cached(&status()).unwrap(), 0xABCD);
+        sesh.write(&status(), 7).unwrap();
+        // Hardware changes the register behind the shadow's back.
+        sesh.sesh.memory_mut()[4..6].copy_from_slice(&[0, 9]);
+        assert_eq!(sesh.read_cached(&status()).unwrap(), 7);
+        assert_eq!(sesh.read(&status()).unwrap(), 9);
+    }
+
+    #[test]
+    fn failed_write_leaves_the_shadow_unchanged() {
+        let mut sesh = ShadowSesh::new(SimSesh::new(8));
+        let checked = Checked::new(control(), Bounds { min: 0, max: 10 });
+        sesh.write(&checked, 3).unwrap();
+        assert!(sesh.write(&checked, 11).is_err());
+        assert_eq!(sesh.last_written(&control()).unwrap(), Some(3));
+        let outside = Resource::<u32, WriteOnly>::new("outside", 8);
+        assert!(sesh.write(&outside, 1).is_err());
+        assert_eq!(sesh.last_written(&outside).unwrap(), None);
+    }
+
+    #[test]
+    fn dump_prints_one_hex_line_per_register() {
+        let mut sesh = ShadowSesh::new(SimSesh::new(8));
+        assert_eq!(sesh.dump().to_string(), "");
+        sesh.write(&status(), 0x0102).unwrap();
+        sesh.write(&control(), 0xDEAD_BEEF).unwrap();
+        assert_eq!(
+            sesh.dump().to_string(),
+            "0x0000: ef be ad de\n0x0004: 01 02\n"
+        );
+    }
+}
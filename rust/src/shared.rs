@@ -0,0 +1,141 @@
+//! Thread-safe shared session handle with scoped exclusive access.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::traits::{Readable, Session, Writable};
+use crate::transaction::{Rollback, Transact, Transaction};
+use crate::{FpgaApiError, FpgaApiResult};
+
+/// Cloneable handle to a session shared between threads. Single accesses lock
+/// the session for just that access; `lock` hands out a guard for groups of
+/// accesses that must not interleave with other threads (e.g. writing a point
+/// and reading its class).
+pub struct SharedSesh<S: Session> {
+    inner: Arc<Mutex<S>>,
+}
+impl<S: Session> SharedSesh<S> {
+    pub fn new(sesh: S) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(sesh)),
+        }
+    }
+    /// Exclusive access to the session until the guard is dropped. Fails if
+    /// another thread panicked while holding the session, since the hardware
+    /// may be left mid-transaction.
+    pub fn lock(&self) -> FpgaApiResult<SeshGuard<'_, S>> {
+        self.inner
+            .lock()
+            .map(SeshGuard)
+            .map_err(|_| FpgaApiError::from("FPGA session was poisoned by a panicking thread!"))
+    }
+    /// Exclusive access to the session even if another thread panicked while
+    /// holding it, for teardown that must run regardless.
+    pub(crate) fn lock_poisoned(&self) -> SeshGuard<'_, S> {
+        SeshGuard(self.inner.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+    /// Run `f` with exclusive access to the session.
+    pub fn with<T, F>(&self, f: F) -> FpgaApiResult<T>
+    where
+        F: FnOnce(&mut S) -> FpgaApiResult<T>,
+    {
+        f(&mut *self.lock()?)
+    }
+    /// Run a transaction while holding the session lock. See
+    /// `Transact::transaction`.
+    pub fn transaction<T, F>(&self, rollback: Rollback, f: F) -> FpgaApiResult<T>
+    where
+        F: FnOnce(&mut Transaction<'_, S>) -> FpgaApiResult<T>,
+    {
+        self.lock()?.transaction(rollback, f)
+    }
+    /// Read a readable resource under a short-lived lock.
+    pub fn read<R: Readable>(&self, resource: &R) -> FpgaApiResult<R::Value> {
+        self.lock()?.read(resource)
+    }
+    /// Write to a writable resource under a short-lived lock.
+    pub fn write<R: Writable>(&self, resource: &R, val: R::Value) -> FpgaApiResult<()> {
+        self.lock()?.write(resource, val)
+    }
+}
+impl<S: Session> Clone for SharedSesh<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+/// Scoped exclusive access to a shared session.
+pub struct SeshGuard<'a, S: Session>(MutexGuard<'a, S>);
+impl<S: Session> Deref for SeshGuard<'_, S> {
+    type Target = S;
+    fn deref(&self) -> &S {
+        &self.0
+    }
+}
+impl<S: Session> DerefMut for SeshGuard<'_, S> {
+    fn deref_mut(&mut self) -> &mut S {
+        &mut self.0
+    }
+}
+
+// The handle exists to share real hardware between threads.
+const _: fn() = || {
+    fn send_sync<T: Send + Sync>() {}
+    send_sync::<SharedSesh<crate::session::MmapSesh>>();
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    use crate::resources::Resource;
+    use crate::session::SimSesh;
+    use crate::traits::ReadWrite;
+
+    fn reg(offset: usize) -> Resource<u32, ReadWrite> {
+        Resource::new("reg", offset)
+    }
+
+    #[test]
+    fn guard_keeps_other_threads_out() {
+        let shared = SharedSesh::new(SimSesh::new(8));
+        let threads: Vec<_> = (0..8u32)
+            .map(|t| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    for i in 0..1000 {
+                        let (a, b) = (t << 16 | i, !(t << 16 | i));
+                        let mut sesh = shared.lock().unwrap();
+                        sesh.write(&reg(0), a).unwrap();
+                        sesh.write(&reg(4), b).unwrap();
+                        assert_eq!(sesh.read(&reg(0)).unwrap(), a);
+                        assert_eq!(sesh.read(&reg(4)).unwrap(), b);
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn poisoned_lock_is_an_error() {
+        let shared = SharedSesh::new(SimSesh::new(8));
+        let other = shared.clone();
+        thread::spawn(move || {
+            let mut sesh = other.lock().unwrap();
+            sesh.write(&reg(0), 1).unwrap();
+            panic!("mid-transaction");
+        })
+        .join()
+        .unwrap_err();
+        assert!(shared.lock().is_err());
+        assert!(shared.read(&reg(0)).is_err());
+        assert_eq!(shared.lock_poisoned().read(&reg(0)).unwrap(), 1);
+    }
+}
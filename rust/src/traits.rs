@@ -1,43 +1,86 @@
 //! Traits and typestates to represent an FPGA session and resources.
 
+use core::time::Duration;
+
 use crate::FpgaApiResult;
 
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+
 /// Trait for FPGA data types.
+///
+/// The encoded ("wire") size is declared by `SIZE` and need not match the
+/// in-memory size of the Rust type (e.g. `bool`, enums or 24-bit fields).
 pub trait Data: Sized {
+    /// Number of bytes in the encoded value.
+    const SIZE: usize;
     /// From little-endian byte slice.
     fn from_le_bytes(bytes: &[u8]) -> FpgaApiResult<Self>;
     /// From big-endian byte slice.
     fn from_be_bytes(bytes: &[u8]) -> FpgaApiResult<Self>;
+    /// Into little-endian byte slice of exactly `SIZE` bytes.
+    fn write_le_bytes(self, bytes: &mut [u8]) -> FpgaApiResult<()>;
+    /// Into big-endian byte slice of exactly `SIZE` bytes.
+    fn write_be_bytes(self, bytes: &mut [u8]) -> FpgaApiResult<()>;
     /// To little-endian byte `Vec`.
-    fn to_le_bytes(self) -> Vec<u8>;
+    #[cfg(feature = "alloc")]
+    fn to_le_bytes(self) -> Vec<u8> {
+        let mut bytes = vec![0; Self::SIZE];
+        self.write_le_bytes(&mut bytes)
+            .expect("Data encoding does not match its SIZE!");
+        bytes
+    }
     /// To big-endian byte `Vec`.
-    fn to_be_bytes(self) -> Vec<u8>;
+    #[cfg(feature = "alloc")]
+    fn to_be_bytes(self) -> Vec<u8> {
+        let mut bytes = vec![0; Self::SIZE];
+        self.write_be_bytes(&mut bytes)
+            .expect("Data encoding does not match its SIZE!");
+        bytes
+    }
 }
 
 /// A readable FPGA resource.
 pub trait Readable {
     /// Data value type of the resource.
     type Value: Data;
+    /// Byte order of the resource on the bus.
+    type Order: ByteOrder;
     /// Memory offset.
     fn byte_offset(&self) -> usize;
-    /// Size in bytes of data type.
+    /// Size in bytes of the encoded data type.
     fn size_in_bytes(&self) -> usize {
-        std::mem::size_of::<Self::Value>()
+        <Self::Value as Data>::SIZE
     }
 }
 /// A writeable FPGA resource.
 pub trait Writable {
     /// Data value type of the resource.
     type Value: Data;
+    /// Byte order of the resource on the bus.
+    type Order: ByteOrder;
     /// Memory offset.
     fn byte_offset(&self) -> usize;
-    /// Size in bytes of data type.
+    /// Size in bytes of the encoded data type.
     fn size_in_bytes(&self) -> usize {
-        std::mem::size_of::<Self::Value>()
+        <Self::Value as Data>::SIZE
+    }
+    /// Check a value before it is written to the bus.
+    fn validate(&self, _value: &Self::Value) -> FpgaApiResult<()> {
+        Ok(())
     }
 }
 
+/// An FPGA resource that is both readable and writable with the same data
+/// type.
+pub trait ReadWritable: Readable + Writable<Value = <Self as Readable>::Value> {}
+impl<R> ReadWritable for R where R: Readable + Writable<Value = <R as Readable>::Value> {}
+
 /// Trait to wrap FPGA hardware with "session" API.
+///
+/// The `Drop` bound is deliberate: every session must decide what hardware
+/// clean up happens when it goes out of scope.
+#[allow(drop_bounds)]
 pub trait Session: Drop {
     /// Read a readable resource.
     fn read<R: Readable>(&self, resource: &R) -> FpgaApiResult<R::Value>;
@@ -45,6 +88,19 @@ pub trait Session: Drop {
     fn write<R: Writable>(&mut self, resource: &R, val: R::Value) -> FpgaApiResult<()>;
 }
 
+/// Trait for sessions whose hardware signals events with an interrupt.
+pub trait Interruptible {
+    /// Unmask the interrupt.
+    fn enable_irq(&mut self) -> FpgaApiResult<()>;
+    /// Mask the interrupt.
+    fn disable_irq(&mut self) -> FpgaApiResult<()>;
+    /// Block until the interrupt fires or `timeout` elapses (`None` waits
+    /// forever). Returns the total interrupt count, or `None` on timeout.
+    fn wait_irq(&mut self, timeout: Option<Duration>) -> FpgaApiResult<Option<u32>>;
+    /// Acknowledge a received interrupt so that it can fire again.
+    fn ack_irq(&mut self) -> FpgaApiResult<()>;
+}
+
 /// Trait to implement typestates for separating read/write entities.
 pub trait IOState {}
 /// Typestate for read-only entity (runtime uninhabitable).
@@ -53,3 +109,53 @@ impl IOState for ReadOnly {}
 /// Typestate for a read/write entity (runtime uninhabitable).
 pub enum ReadWrite {}
 impl IOState for ReadWrite {}
+/// Typestate for a write-only entity (runtime uninhabitable).
+pub enum WriteOnly {}
+impl IOState for WriteOnly {}
+
+/// Trait to implement typestates for the byte order of an entity.
+pub trait ByteOrder {
+    /// Decode a value from bytes in this byte order.
+    fn decode<D: Data>(bytes: &[u8]) -> FpgaApiResult<D>;
+    /// Encode a value into bytes in this byte order.
+    fn encode<D: Data>(val: D, bytes: &mut [u8]) -> FpgaApiResult<()>;
+}
+/// Typestate for a little-endian entity (runtime uninhabitable).
+pub enum LittleEndian {}
+impl ByteOrder for LittleEndian {
+    fn decode<D: Data>(bytes: &[u8]) -> FpgaApiResult<D> {
+        D::from_le_bytes(bytes)
+    }
+    fn encode<D: Data>(val: D, bytes: &mut [u8]) -> FpgaApiResult<()> {
+        val.write_le_bytes(bytes)
+    }
+}
+/// Typestate for a big-endian entity (runtime uninhabitable).
+pub enum BigEndian {}
+impl ByteOrder for BigEndian {
+    fn decode<D: Data>(bytes: &[u8]) -> FpgaApiResult<D> {
+        D::from_be_bytes(bytes)
+    }
+    fn encode<D: Data>(val: D, bytes: &mut [u8]) -> FpgaApiResult<()> {
+        val.write_be_bytes(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::Resource;
+    use crate::session::{read_bytes, write_bytes};
+
+    #[test]
+    fn big_endian_resource_writes_msb_first() {
+        let mut mem = [0u8; 8];
+        let be = Resource::<u32, ReadWrite, BigEndian>::new("be", 0);
+        let le = Resource::<u32, ReadWrite, LittleEndian>::new("le", 4);
+        write_bytes(&mut mem, &be, 0x0102_0304).unwrap();
+        write_bytes(&mut mem, &le, 0x0102_0304).unwrap();
+        assert_eq!(mem, [1, 2, 3, 4, 4, 3, 2, 1]);
+        assert_eq!(read_bytes(&mem, &be).unwrap(), 0x0102_0304);
+        assert_eq!(read_bytes(&mem, &le).unwrap(), 0x0102_0304);
+    }
+}
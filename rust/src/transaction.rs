@@ -0,0 +1,218 @@
+//! Transactional grouping of register accesses.
+
+use crate::traits::{ReadWritable, Readable, Session, Writable};
+use crate::FpgaApiResult;
+
+/// Whether registers written in a failed transaction are restored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rollback {
+    /// Leave registers as the failed transaction wrote them.
+    Disabled,
+    /// Restore registers written with `Transaction::write_restorable` to the
+    /// values read before each write.
+    Enabled,
+}
+
+type Undo<S> = Box<dyn FnOnce(&mut S) -> FpgaApiResult<()>>;
+
+/// Exclusive access to a session for the duration of a transaction.
+pub struct Transaction<'a, S: Session> {
+    sesh: &'a mut S,
+    rollback: Rollback,
+    undo: Vec<Undo<S>>,
+}
+impl<S: Session> Transaction<'_, S> {
+    /// Read a readable resource.
+    pub fn read<R: Readable>(&self, resource: &R) -> FpgaApiResult<R::Value> {
+        self.sesh.read(resource)
+    }
+    /// Write to a writable resource. The write is not undone if the
+    /// transaction fails (e.g. for write-only registers).
+    pub fn write<R: Writable>(&mut self, resource: &R, val: R::Value) -> FpgaApiResult<()> {
+        self.sesh.write(resource, val)
+    }
+    /// Write to a read/write resource, first saving its current value if
+    /// rollback is enabled.
+    pub fn write_restorable<R>(
+        &mut self,
+        resource: &R,
+        val: <R as Readable>::Value,
+    ) -> FpgaApiResult<()>
+    where
+        R: ReadWritable + Clone + 'static,
+    {
+        if self.rollback == Rollback::Enabled {
+            let prev = self.sesh.read(resource)?;
+            let resource = resource.clone();
+            self.undo
+                .push(Box::new(move |sesh: &mut S| sesh.write(&resource, prev)));
+        }
+        self.sesh.write(resource, val)
+    }
+    /// Restore saved values, most recent write first. Every register is
+    /// attempted; the first failure is returned.
+    fn roll_back(self) -> FpgaApiResult<()> {
+        let mut result = Ok(());
+        for undo in self.undo.into_iter().rev() {
+            let restored = undo(self.sesh);
+            if result.is_ok() {
+                result = restored;
+            }
+        }
+        result
+    }
+}
+
+/// Error of a transaction whose rollback failed as well.
+#[derive(Debug)]
+pub struct RollbackError {
+    /// Error that aborted the transaction.
+    pub cause: crate::FpgaApiError,
+    /// First error while restoring registers.
+    pub rollback: crate::FpgaApiError,
+}
+impl std::fmt::Display for RollbackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Transaction failed ({}) and rollback failed ({})",
+            self.cause, self.rollback
+        )
+    }
+}
+impl std::error::Error for RollbackError {}
+
+/// Transactions over any session.
+pub trait Transact: Session + Sized {
+    /// Run `f` with exclusive access to the session. If `f` fails and rollback
+    /// is enabled, registers written through `write_restorable` are restored.
+    fn transaction<T, F>(&mut self, rollback: Rollback, f: F) -> FpgaApiResult<T>
+    where
+        F: FnOnce(&mut Transaction<'_, Self>) -> FpgaApiResult<T>;
+}
+impl<S: Session> Transact for S {
+    fn transaction<T, F>(&mut self, rollback: Rollback, f: F) -> FpgaApiResult<T>
+    where
+        F: FnOnce(&mut Transaction<'_, Self>) -> FpgaApiResult<T>,
+    {
+        let mut txn = Transaction {
+            sesh: self,
+            rollback,
+            undo: Vec::new(),
+        };
+        match f(&mut txn) {
+            Ok(val) => Ok(val),
+            Err(cause) => match txn.roll_back() {
+                Ok(()) => Err(cause),
+                Err(rollback) => Err(RollbackError { cause, rollback }.into()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::Resource;
+    use crate::session::SimSesh;
+    use crate::traits::{LittleEndian, ReadWrite, WriteOnly};
+    use crate::FpgaApiError;
+
+    fn reg(offset: usize) -> Resource<u32, ReadWrite> {
+        Resource::new("reg", offset)
+    }
+
+    #[test]
+    fn commit_keeps_all_writes() {
+        let mut sesh = SimSesh::new(12);
+        let command = Resource::<u32, WriteOnly>::new("command", 8);
+        let out = sesh
+            .transaction(Rollback::Enabled, |txn| {
+                txn.write_restorable(&reg(0), 1)?;
+                txn.write(&reg(4), 2)?;
+                txn.write(&command, 3)?;
+                txn.read(&reg(0))
+            })
+            .unwrap();
+        assert_eq!(out, 1);
+        assert_eq!(sesh.read(&reg(0)).unwrap(), 1);
+        assert_eq!(sesh.read(&reg(4)).unwrap(), 2);
+        assert_eq!(&sesh.memory()[8..], &3u32.to_le_bytes());
+    }
+
+    #[test]
+    fn failure_restores_in_reverse_order() {
+        let mut sesh = SimSesh::new(8);
+        sesh.write(&reg(0), 10).unwrap();
+        sesh.write(&reg(4), 20).unwrap();
+        let err = sesh
+            .transaction(Rollback::Enabled, |txn| {
+                txn.write_restorable(&reg(0), 1)?;
+                txn.write_restorable(&reg(4), 2)?;
+                // Saves 1; undoing in write order would end on this value.
+                txn.write_restorable(&reg(0), 3)?;
+                Err::<(), _>(FpgaApiError::from("abort"))
+            })
+            .unwrap_err();
+        assert_eq!(err.to_string(), "abort");
+        assert_eq!(sesh.read(&reg(0)).unwrap(), 10);
+        assert_eq!(sesh.read(&reg(4)).unwrap(), 20);
+    }
+
+    #[test]
+    fn plain_writes_and_disabled_rollback_are_not_restored() {
+        let mut sesh = SimSesh::new(8);
+        let _ = sesh.transaction(Rollback::Enabled, |txn| {
+            txn.write(&reg(0), 1)?;
+            Err::<(), _>(FpgaApiError::from("abort"))
+        });
+        let _ = sesh.transaction(Rollback::Disabled, |txn| {
+            txn.write_restorable(&reg(4), 2)?;
+            Err::<(), _>(FpgaApiError::from("abort"))
+        });
+        assert_eq!(sesh.read(&reg(0)).unwrap(), 1);
+        assert_eq!(sesh.read(&reg(4)).unwrap(), 2);
+    }
+
+    /// Register that rejects writing zero, its reset value.
+    #[derive(Clone)]
+    struct NonZero;
+    impl Readable for NonZero {
+        type Value = u32;
+        type Order = LittleEndian;
+        fn byte_offset(&self) -> usize {
+            0
+        }
+    }
+    impl Writable for NonZero {
+        type Value = u32;
+        type Order = LittleEndian;
+        fn byte_offset(&self) -> usize {
+            0
+        }
+        fn validate(&self, value: &u32) -> FpgaApiResult<()> {
+            match value {
+                0 => Err(FpgaApiError::from("zero")),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn failed_restore_reports_both_errors() {
+        let mut sesh = SimSesh::new(8);
+        let err = sesh
+            .transaction(Rollback::Enabled, |txn| {
+                txn.write_restorable(&reg(4), 7)?;
+                txn.write_restorable(&NonZero, 5)?;
+                Err::<(), _>(FpgaApiError::from("abort"))
+            })
+            .unwrap_err();
+        let err = err.downcast_ref::<RollbackError>().unwrap();
+        assert_eq!(err.cause.to_string(), "abort");
+        assert_eq!(err.rollback.to_string(), "zero");
+        // The remaining registers are still restored.
+        assert_eq!(sesh.read(&NonZero).unwrap(), 5);
+        assert_eq!(sesh.read(&reg(4)).unwrap(), 0);
+    }
+}
@@ -0,0 +1,392 @@
+//! Session backend for Linux userspace I/O (UIO) devices.
+//!
+//! Unlike `/dev/mem`, a UIO device only exposes the regions the kernel
+//! assigned to it and does not require root. Devices are discovered by name
+//! through `/sys/class/uio/uioN/name` and `maps/mapM/{addr,size,offset}`;
+//! region `M` is mapped from `/dev/uioN` at an offset of `M` pages, and its
+//! registers start `offset` bytes into that mapping when the physical address
+//! is not page aligned.
+//!
+//! Interrupts follow the UIO protocol: a blocking 4-byte read returns the
+//! interrupt count, and writing `1`/`0` re-enables/masks the interrupt.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use memmap::{MmapMut, MmapOptions};
+
+use crate::session::{read_bytes, write_bytes};
+use crate::traits::{Interruptible, Readable, Session, Writable};
+use crate::{FpgaApiError, FpgaApiResult};
+
+const SYSFS_UIO_ROOT: &str = "/sys/class/uio";
+const DEV_ROOT: &str = "/dev";
+
+/// A memory region of a UIO device, from `maps/mapM/{addr,size,offset}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UioMap {
+    pub index: usize,
+    pub addr: u64,
+    pub size: usize,
+    /// Offset of the region's first byte inside its first page.
+    pub offset: usize,
+}
+
+/// A UIO device discovered through sysfs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UioDevice {
+    pub name: String,
+    pub node: PathBuf,
+    pub maps: Vec<UioMap>,
+}
+impl UioDevice {
+    /// Find the UIO device with the given name.
+    pub fn find(name: &str) -> FpgaApiResult<Self> {
+        Self::find_in(Path::new(SYSFS_UIO_ROOT), Path::new(DEV_ROOT), name)
+    }
+    /// Find the UIO device with the given name under alternative sysfs and
+    /// device node roots (e.g. a fake tree standing in for the kernel's).
+    pub fn find_in(sysfs_root: &Path, dev_root: &Path, name: &str) -> FpgaApiResult<Self> {
+        let mut entries = fs::read_dir(sysfs_root)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            // Skip entries without a readable `name` rather than failing the
+            // whole lookup.
+            let dev_name = match fs::read_to_string(entry.path().join("name")) {
+                Ok(dev_name) => dev_name,
+                Err(_) => continue,
+            };
+            if dev_name.trim() == name {
+                return Ok(Self {
+                    name: name.to_string(),
+                    node: dev_root.join(entry.file_name()),
+                    maps: read_maps(&entry.path().join("maps"))?,
+                });
+            }
+        }
+        Err(FpgaApiError::from(format!(
+            "No UIO device named {:?} in {:?}",
+            name, sysfs_root
+        )))
+    }
+    /// Open the device node and map region `index` into a session.
+    pub fn open(&self, index: usize) -> FpgaApiResult<UioSesh> {
+        let map = self
+            .maps
+            .iter()
+            .find(|m| m.index == index)
+            .ok_or_else(|| format!("UIO device {:?} has no map{}", self.name, index))?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(false)
+            .open(&self.node)?;
+        let mmap = unsafe {
+            MmapOptions::new()
+                .offset((index * page_size()) as u64)
+                .len(map.offset + map.size)
+                .map_mut(&file)?
+        };
+        Ok(UioSesh {
+            irq: UioIrq::new(file),
+            map: *map,
+            mmap,
+        })
+    }
+}
+
+/// Interrupt handling for a UIO file descriptor. Any readable and writable
+/// descriptor speaking the same protocol works, e.g. one end of a
+/// `UnixStream` pair standing in for the device node.
+pub struct UioIrq<F: AsRawFd + Read + Write> {
+    fd: F,
+}
+impl<F: AsRawFd + Read + Write> UioIrq<F> {
+    pub fn new(fd: F) -> Self {
+        Self { fd }
+    }
+    /// The wrapped file descriptor.
+    pub fn get_ref(&self) -> &F {
+        &self.fd
+    }
+    fn write_control(&mut self, enable: u32) -> FpgaApiResult<()> {
+        self.fd.write_all(&enable.to_ne_bytes())?;
+        Ok(())
+    }
+    /// Poll for readability, retrying on signal interruption.
+    fn poll_readable(&self, timeout: Option<Duration>) -> FpgaApiResult<bool> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let timeout_ms = match deadline {
+                Some(d) => {
+                    let remaining = d.saturating_duration_since(Instant::now());
+                    // Round up so sub-millisecond timeouts still wait.
+                    remaining.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32
+                }
+                None => -1,
+            };
+            let mut pfd = libc::pollfd {
+                fd: self.fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            match unsafe { libc::poll(&mut pfd, 1, timeout_ms) } {
+                -1 => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err.into());
+                    }
+                }
+                0 => return Ok(false),
+                _ => return Ok(true),
+            }
+        }
+    }
+}
+impl<F: AsRawFd + Read + Write> Interruptible for UioIrq<F> {
+    fn enable_irq(&mut self) -> FpgaApiResult<()> {
+        self.write_control(1)
+    }
+    fn disable_irq(&mut self) -> FpgaApiResult<()> {
+        self.write_control(0)
+    }
+    fn wait_irq(&mut self, timeout: Option<Duration>) -> FpgaApiResult<Option<u32>> {
+        if !self.poll_readable(timeout)? {
+            return Ok(None);
+        }
+        let mut count = [0; 4];
+        self.fd.read_exact(&mut count)?;
+        Ok(Some(u32::from_ne_bytes(count)))
+    }
+    fn ack_irq(&mut self) -> FpgaApiResult<()> {
+        // The kernel masks the interrupt when it fires; re-enabling it is the
+        // acknowledgement.
+        self.write_control(1)
+    }
+}
+
+/// Session for FPGA I/O through a mapped UIO device region.
+pub struct UioSesh {
+    irq: UioIrq<File>,
+    map: UioMap,
+    mmap: MmapMut,
+}
+impl UioSesh {
+    /// The region this session maps.
+    pub fn map(&self) -> UioMap {
+        self.map
+    }
+    /// The open UIO device node.
+    pub fn file(&self) -> &File {
+        self.irq.get_ref()
+    }
+}
+impl Session for UioSesh {
+    fn read<R: Readable>(&self, resource: &R) -> FpgaApiResult<R::Value> {
+        read_bytes(&self.mmap[self.map.offset..], resource)
+    }
+    fn write<R: Writable>(&mut self, resource: &R, val: R::Value) -> FpgaApiResult<()> {
+        write_bytes(&mut self.mmap[self.map.offset..], resource, val)
+    }
+}
+impl Interruptible for UioSesh {
+    fn enable_irq(&mut self) -> FpgaApiResult<()> {
+        self.irq.enable_irq()
+    }
+    fn disable_irq(&mut self) -> FpgaApiResult<()> {
+        self.irq.disable_irq()
+    }
+    fn wait_irq(&mut self, timeout: Option<Duration>) -> FpgaApiResult<Option<u32>> {
+        self.irq.wait_irq(timeout)
+    }
+    fn ack_irq(&mut self) -> FpgaApiResult<()> {
+        self.irq.ack_irq()
+    }
+}
+impl Drop for UioSesh {
+    fn drop(&mut self) {}
+}
+
+fn read_maps(maps_dir: &Path) -> FpgaApiResult<Vec<UioMap>> {
+    let mut maps = Vec::new();
+    // Devices without memory regions have no `maps` directory.
+    if !maps_dir.is_dir() {
+        return Ok(maps);
+    }
+    for entry in fs::read_dir(maps_dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let index = match file_name.to_str().and_then(|n| n.strip_prefix("map")) {
+            Some(i) => i.parse::<usize>()?,
+            None => continue,
+        };
+        // Kernels before 3.10 do not report `offset`.
+        let offset = entry.path().join("offset");
+        maps.push(UioMap {
+            index,
+            addr: read_hex(&entry.path().join("addr"))?,
+            size: read_hex(&entry.path().join("size"))? as usize,
+            offset: if offset.exists() {
+                read_hex(&offset)? as usize
+            } else {
+                0
+            },
+        });
+    }
+    maps.sort_by_key(|m| m.index);
+    Ok(maps)
+}
+
+/// Read a sysfs attribute formatted as `0x...`.
+fn read_hex(path: &Path) -> FpgaApiResult<u64> {
+    let contents = fs::read_to_string(path)?;
+    let trimmed = contents.trim();
+    let digits = trimmed
+        .strip_prefix("0x")
+        .or_else(|| trimmed.strip_prefix("0X"))
+        .unwrap_or(trimmed);
+    Ok(u64::from_str_radix(digits, 16)?)
+}
+
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::Resource;
+    use crate::traits::ReadWrite;
+    use std::os::unix::net::UnixStream;
+
+    /// Fake `/sys/class/uio` with an unrelated `uio0` and a `uio1` named
+    /// `sysid` whose `map0` starts `0x30` bytes into its page. `uio00` has no
+    /// `name`, like a device that is going away during the lookup.
+    fn fake_sysfs() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("uio00")).unwrap();
+        let uio0 = root.path().join("uio0");
+        fs::create_dir_all(&uio0).unwrap();
+        fs::write(uio0.join("name"), "other\n").unwrap();
+        let maps = root.path().join("uio1/maps");
+        for (map, addr, size, offset) in [
+            ("map0", "0xff210030", "0x8", Some("0x30")),
+            ("map1", "0xc0020000", "0x40", None),
+        ] {
+            fs::create_dir_all(maps.join(map)).unwrap();
+            fs::write(maps.join(map).join("addr"), format!("{}\n", addr)).unwrap();
+            fs::write(maps.join(map).join("size"), format!("{}\n", size)).unwrap();
+            if let Some(offset) = offset {
+                fs::write(maps.join(map).join("offset"), format!("{}\n", offset)).unwrap();
+            }
+        }
+        fs::write(root.path().join("uio1/name"), "sysid\n").unwrap();
+        root
+    }
+
+    #[test]
+    fn finds_device_by_name_and_parses_maps() {
+        let sysfs = fake_sysfs();
+        let dev = UioDevice::find_in(sysfs.path(), Path::new("/dev"), "sysid").unwrap();
+        assert_eq!(dev.name, "sysid");
+        assert_eq!(dev.node, Path::new("/dev/uio1"));
+        assert_eq!(
+            dev.maps,
+            vec![
+                UioMap {
+                    index: 0,
+                    addr: 0xFF21_0030,
+                    size: 8,
+                    offset: 0x30,
+                },
+                UioMap {
+                    index: 1,
+                    addr: 0xC002_0000,
+                    size: 0x40,
+                    offset: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_name_is_an_error() {
+        let sysfs = fake_sysfs();
+        let err = UioDevice::find_in(sysfs.path(), Path::new("/dev"), "point_nn").unwrap_err();
+        assert!(err.to_string().contains("point_nn"));
+    }
+
+    #[test]
+    fn unknown_map_is_an_error() {
+        let sysfs = fake_sysfs();
+        let dev = UioDevice::find_in(sysfs.path(), Path::new("/dev"), "sysid").unwrap();
+        assert!(dev.open(2).is_err());
+    }
+
+    #[test]
+    fn session_reads_and_writes_from_the_map_offset() {
+        let sysfs = fake_sysfs();
+        let dev_root = tempfile::tempdir().unwrap();
+        // A regular file stands in for `/dev/uio1`.
+        let node = dev_root.path().join("uio1");
+        let mut page = vec![0u8; 0x38];
+        page[0x30..0x34].copy_from_slice(&0xDE10_DE10u32.to_le_bytes());
+        fs::write(&node, &page).unwrap();
+
+        let dev = UioDevice::find_in(sysfs.path(), dev_root.path(), "sysid").unwrap();
+        let mut sesh = dev.open(0).unwrap();
+        assert_eq!(sesh.map(), dev.maps[0]);
+        let id = Resource::<u32, ReadWrite>::new("id", 0);
+        let timestamp = Resource::<u32, ReadWrite>::new("timestamp", 4);
+        assert_eq!(sesh.read(&id).unwrap(), 0xDE10_DE10);
+        sesh.write(&timestamp, 0x0102_0304).unwrap();
+        assert_eq!(sesh.read(&timestamp).unwrap(), 0x0102_0304);
+        // The register block ends with the region, not the page.
+        assert!(sesh
+            .read(&Resource::<u32, ReadWrite>::new("past", 8))
+            .is_err());
+        drop(sesh);
+
+        assert_eq!(&fs::read(&node).unwrap()[0x34..0x38], &[4, 3, 2, 1]);
+    }
+
+    fn read_control(peer: &mut UnixStream) -> u32 {
+        let mut word = [0; 4];
+        peer.read_exact(&mut word).unwrap();
+        u32::from_ne_bytes(word)
+    }
+
+    #[test]
+    fn wait_irq_times_out_without_an_interrupt() {
+        let (fd, _peer) = UnixStream::pair().unwrap();
+        let mut irq = UioIrq::new(fd);
+        let start = Instant::now();
+        assert_eq!(irq.wait_irq(Some(Duration::from_millis(20))).unwrap(), None);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn wait_irq_returns_the_interrupt_count() {
+        let (fd, mut peer) = UnixStream::pair().unwrap();
+        let mut irq = UioIrq::new(fd);
+        peer.write_all(&7u32.to_ne_bytes()).unwrap();
+        assert_eq!(irq.wait_irq(Some(Duration::from_secs(5))).unwrap(), Some(7));
+        peer.write_all(&8u32.to_ne_bytes()).unwrap();
+        assert_eq!(irq.wait_irq(None).unwrap(), Some(8));
+    }
+
+    #[test]
+    fn control_writes_follow_the_uio_protocol() {
+        let (fd, mut peer) = UnixStream::pair().unwrap();
+        let mut irq = UioIrq::new(fd);
+        irq.enable_irq().unwrap();
+        assert_eq!(read_control(&mut peer), 1);
+        irq.disable_irq().unwrap();
+        assert_eq!(read_control(&mut peer), 0);
+        irq.ack_irq().unwrap();
+        assert_eq!(read_control(&mut peer), 1);
+    }
+}
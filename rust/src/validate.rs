@@ -0,0 +1,281 @@
+//! Value validation for writable resources.
+//!
+//! `Checked` wraps a resource with a `Constraint` that every session checks
+//! before the bus write. Rejected values are returned as a `ValidationError`
+//! and counted.
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::traits::{Readable, Writable};
+#[cfg(not(feature = "std"))]
+use crate::FpgaApiError;
+use crate::FpgaApiResult;
+
+/// Why a value was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    BelowMin,
+    AboveMax,
+    NotAllowed,
+    /// A predicate failed; carries its description.
+    Predicate(&'static str),
+}
+impl core::fmt::Display for Violation {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Violation::BelowMin => f.write_str("value is below the minimum"),
+            Violation::AboveMax => f.write_str("value is above the maximum"),
+            Violation::NotAllowed => f.write_str("value is not one of the allowed values"),
+            Violation::Predicate(desc) => write!(f, "value violates \"{}\"", desc),
+        }
+    }
+}
+
+/// Value rejected before it reached the hardware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationError {
+    /// Byte offset of the resource.
+    pub offset: usize,
+    pub violation: Violation,
+}
+impl core::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "Write to byte offset {} rejected: {}",
+            self.offset, self.violation
+        )
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for ValidationError {}
+#[cfg(not(feature = "std"))]
+impl From<ValidationError> for FpgaApiError {
+    fn from(_: ValidationError) -> Self {
+        FpgaApiError::from("Write rejected by resource validator!")
+    }
+}
+
+/// Rule a value must satisfy to be written.
+pub trait Constraint<V> {
+    fn check(&self, value: &V) -> Result<(), Violation>;
+}
+
+/// Inclusive `min..=max` range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds<V> {
+    pub min: V,
+    pub max: V,
+}
+impl<V: PartialOrd> Constraint<V> for Bounds<V> {
+    fn check(&self, value: &V) -> Result<(), Violation> {
+        use core::cmp::Ordering::{Greater, Less};
+        // Incomparable values (NaN) are rejected too.
+        match (value.partial_cmp(&self.min), value.partial_cmp(&self.max)) {
+            (Some(Less), _) | (None, _) => Err(Violation::BelowMin),
+            (_, Some(Greater)) | (_, None) => Err(Violation::AboveMax),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Set of allowed values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OneOf<V: 'static>(pub &'static [V]);
+impl<V: PartialEq> Constraint<V> for OneOf<V> {
+    fn check(&self, value: &V) -> Result<(), Violation> {
+        if self.0.contains(value) {
+            Ok(())
+        } else {
+            Err(Violation::NotAllowed)
+        }
+    }
+}
+
+/// Arbitrary predicate, with a description for error messages.
+#[derive(Clone, Copy)]
+pub struct Predicate<F> {
+    pub description: &'static str,
+    pub f: F,
+}
+impl<V, F: Fn(&V) -> bool> Constraint<V> for Predicate<F> {
+    fn check(&self, value: &V) -> Result<(), Violation> {
+        if (self.f)(value) {
+            Ok(())
+        } else {
+            Err(Violation::Predicate(self.description))
+        }
+    }
+}
+
+/// Resource whose writes must satisfy a constraint. Clones share the
+/// violation counter.
+pub struct Checked<R, C> {
+    resource: R,
+    constraint: C,
+    violations: Arc<AtomicUsize>,
+}
+impl<R, C> Checked<R, C> {
+    pub fn new(resource: R, constraint: C) -> Self {
+        Self {
+            resource,
+            constraint,
+            violations: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+    /// Number of writes rejected so far.
+    pub fn violations(&self) -> usize {
+        self.violations.load(Ordering::Relaxed)
+    }
+    /// The wrapped resource.
+    pub fn get(&self) -> &R {
+        &self.resource
+    }
+}
+impl<R: Clone, C: Clone> Clone for Checked<R, C> {
+    fn clone(&self) -> Self {
+        Self {
+            resource: self.resource.clone(),
+            constraint: self.constraint.clone(),
+            violations: Arc::clone(&self.violations),
+        }
+    }
+}
+impl<R: core::fmt::Display, C> core::fmt::Display for Checked<R, C> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.resource.fmt(f)
+    }
+}
+impl<R: Readable, C> Readable for Checked<R, C> {
+    type Value = R::Value;
+    type Order = R::Order;
+    fn byte_offset(&self) -> usize {
+        self.resource.byte_offset()
+    }
+    fn size_in_bytes(&self) -> usize {
+        self.resource.size_in_bytes()
+    }
+}
+impl<R: Writable, C: Constraint<R::Value>> Writable for Checked<R, C> {
+    type Value = R::Value;
+    type Order = R::Order;
+    fn byte_offset(&self) -> usize {
+        self.resource.byte_offset()
+    }
+    fn size_in_bytes(&self) -> usize {
+        self.resource.size_in_bytes()
+    }
+    fn validate(&self, value: &Self::Value) -> FpgaApiResult<()> {
+        self.resource.validate(value)?;
+        self.constraint.check(value).map_err(|violation| {
+            self.violations.fetch_add(1, Ordering::Relaxed);
+            ValidationError {
+                offset: self.resource.byte_offset(),
+                violation,
+            }
+            .into()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::Resource;
+    use crate::session::SimSesh;
+    use crate::traits::{ReadWrite, Session};
+
+    #[test]
+    fn bounds_are_inclusive() {
+        let bounds = Bounds { min: -2, max: 5 };
+        assert_eq!(bounds.check(&-2), Ok(()));
+        assert_eq!(bounds.check(&5), Ok(()));
+        assert_eq!(bounds.check(&-3), Err(Violation::BelowMin));
+        assert_eq!(bounds.check(&6), Err(Violation::AboveMax));
+    }
+
+    #[test]
+    fn bounds_reject_nan() {
+        let bounds = Bounds {
+            min: -1.0f32,
+            max: 1.0,
+        };
+        assert_eq!(bounds.check(&0.5), Ok(()));
+        assert!(bounds.check(&f32::NAN).is_err());
+        assert_eq!(bounds.check(&f32::INFINITY), Err(Violation::AboveMax));
+        assert_eq!(bounds.check(&f32::NEG_INFINITY), Err(Violation::BelowMin));
+    }
+
+    #[test]
+    fn one_of_allows_only_listed_values() {
+        let modes = OneOf(&[1u8, 2, 4]);
+        assert_eq!(modes.check(&2), Ok(()));
+        assert_eq!(modes.check(&3), Err(Violation::NotAllowed));
+        assert_eq!(OneOf::<u8>(&[]).check(&0), Err(Violation::NotAllowed));
+    }
+
+    #[test]
+    fn predicate_carries_its_description() {
+        let even = Predicate {
+            description: "even",
+            f: |v: &u32| v.is_multiple_of(2),
+        };
+        assert_eq!(even.check(&4), Ok(()));
+        assert_eq!(even.check(&3), Err(Violation::Predicate("even")));
+    }
+
+    #[test]
+    fn clones_share_the_violation_counter() {
+        let mut sesh = SimSesh::new(4);
+        let reg = Checked::new(
+            Resource::<u32, ReadWrite>::new("reg", 0),
+            Bounds { min: 0, max: 10 },
+        );
+        let clone = reg.clone();
+        assert!(sesh.write(&reg, 11).is_err());
+        assert!(sesh.write(&clone, 12).is_err());
+        sesh.write(&clone, 10).unwrap();
+        assert_eq!(reg.violations(), 2);
+        assert_eq!(clone.violations(), 2);
+        // A fresh wrapper has its own counter.
+        let other = Checked::new(*reg.get(), Bounds { min: 0, max: 10 });
+        assert_eq!(other.violations(), 0);
+    }
+
+    #[test]
+    fn sim_sesh_rejects_before_writing() {
+        let mut sesh = SimSesh::new(8);
+        sesh.memory_mut().copy_from_slice(&[0xAA; 8]);
+        let reg = Checked::new(
+            Resource::<u32, ReadWrite>::new("reg", 4),
+            Bounds { min: 0, max: 10 },
+        );
+        assert!(sesh.write(&reg, 11).is_err());
+        assert_eq!(sesh.memory(), &[0xAA; 8]);
+        sesh.write(&reg, 3).unwrap();
+        assert_eq!(sesh.read(&reg).unwrap(), 3);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn mmap_sesh_rejects_before_writing() {
+        use crate::session::MmapSesh;
+
+        let mut mmap = memmap::MmapMut::map_anon(8).unwrap();
+        mmap.copy_from_slice(&[0xAA; 8]);
+        let mut sesh = MmapSesh::with_offset(mmap, 0).unwrap();
+        let reg = Checked::new(Resource::<u32, ReadWrite>::new("reg", 0), OneOf(&[1, 2, 4]));
+        let err = sesh.write(&reg, 3).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ValidationError>(),
+            Some(&ValidationError {
+                offset: 0,
+                violation: Violation::NotAllowed,
+            })
+        );
+        assert_eq!(sesh.read(&reg).unwrap(), 0xAAAA_AAAA);
+        sesh.write(&reg, 4).unwrap();
+        assert_eq!(sesh.read(&reg).unwrap(), 4);
+    }
+}
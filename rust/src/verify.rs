@@ -0,0 +1,207 @@
+//! Read-back verification of register writes.
+//!
+//! `write_verified` reads a `ReadWritable` resource back after writing it, to
+//! catch bridge misconfiguration and bad bitstreams early instead of
+//! silently computing on whatever the hardware latched.
+
+use std::fmt::{self, Debug};
+
+use crate::poll::Backoff;
+use crate::traits::{ReadWritable, Readable, Session};
+use crate::FpgaApiResult;
+
+/// How hard to try before reporting a mismatch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyPolicy {
+    /// Number of times to write again after the first mismatch.
+    pub retries: u32,
+    /// Delay before each retry.
+    pub backoff: Backoff,
+}
+impl VerifyPolicy {
+    /// Verify once, without retrying.
+    pub const fn once() -> Self {
+        Self {
+            retries: 0,
+            backoff: Backoff::Spin,
+        }
+    }
+    pub const fn with_retries(retries: u32, backoff: Backoff) -> Self {
+        Self { retries, backoff }
+    }
+}
+impl Default for VerifyPolicy {
+    fn default() -> Self {
+        Self::once()
+    }
+}
+
+/// Value read back after the last write did not match the value written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriteVerifyError<V> {
+    /// Byte offset of the resource.
+    pub offset: usize,
+    pub expected: V,
+    pub actual: V,
+    /// Number of writes performed.
+    pub attempts: u32,
+}
+impl<V: Debug> fmt::Display for WriteVerifyError<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Write to byte offset {} did not stick after {} attempts: wrote {:?}, read back {:?}",
+            self.offset, self.attempts, self.expected, self.actual
+        )
+    }
+}
+impl<V: Debug> std::error::Error for WriteVerifyError<V> {}
+
+/// Extension trait adding verified writes to every session.
+pub trait VerifyWrite: Session {
+    /// Write `val` to `resource` and read it back, writing again up to
+    /// `policy.retries` times while the value read differs. Fails with a
+    /// `WriteVerifyError` if it never matches.
+    fn write_verified<R>(
+        &mut self,
+        resource: &R,
+        val: <R as Readable>::Value,
+        policy: VerifyPolicy,
+    ) -> FpgaApiResult<()>
+    where
+        R: ReadWritable,
+        <R as Readable>::Value: Clone + PartialEq + Debug + Send + Sync + 'static;
+}
+impl<S: Session> VerifyWrite for S {
+    fn write_verified<R>(
+        &mut self,
+        resource: &R,
+        val: <R as Readable>::Value,
+        policy: VerifyPolicy,
+    ) -> FpgaApiResult<()>
+    where
+        R: ReadWritable,
+        <R as Readable>::Value: Clone + PartialEq + Debug + Send + Sync + 'static,
+    {
+        let mut attempts = 0;
+        loop {
+            if attempts > 0 {
+                std::thread::sleep(policy.backoff.delay(attempts));
+            }
+            self.write(resource, val.clone())?;
+            attempts += 1;
+            let actual = self.read(resource)?;
+            if actual == val {
+                return Ok(());
+            }
+            if attempts > policy.retries {
+                return Err(WriteVerifyError {
+                    offset: Readable::byte_offset(resource),
+                    expected: val,
+                    actual,
+                    attempts,
+                }
+                .into());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::resources::Resource;
+    use crate::session::SimSesh;
+    use crate::traits::{ReadWrite, Writable};
+
+    /// Register block that drops every write before the `latch_on`-th one,
+    /// like a core that is still in reset.
+    struct Sticky {
+        sesh: SimSesh,
+        latch_on: u32,
+        writes: u32,
+    }
+    impl Sticky {
+        fn new(latch_on: u32) -> Self {
+            Self {
+                sesh: SimSesh::new(8),
+                latch_on,
+                writes: 0,
+            }
+        }
+    }
+    impl Session for Sticky {
+        fn read<R: Readable>(&self, resource: &R) -> FpgaApiResult<R::Value> {
+            self.sesh.read(resource)
+        }
+        fn write<R: Writable>(&mut self, resource: &R, val: R::Value) -> FpgaApiResult<()> {
+            self.writes += 1;
+            if self.writes >= self.latch_on {
+                self.sesh.write(resource, val)?;
+            }
+            Ok(())
+        }
+    }
+    impl Drop for Sticky {
+        fn drop(&mut self) {}
+    }
+
+    fn reg() -> Resource<u32, ReadWrite> {
+        Resource::new("reg", 4)
+    }
+
+    fn mismatch(err: crate::FpgaApiError) -> WriteVerifyError<u32> {
+        err.downcast_ref::<WriteVerifyError<u32>>().unwrap().clone()
+    }
+
+    #[test]
+    fn first_write_that_sticks_succeeds() {
+        let mut sesh = Sticky::new(1);
+        sesh.write_verified(&reg(), 5, VerifyPolicy::once())
+            .unwrap();
+        assert_eq!(sesh.writes, 1);
+    }
+
+    #[test]
+    fn ignored_writes_report_expected_actual_and_attempts() {
+        let mut sesh = Sticky::new(u32::MAX);
+        let err = sesh
+            .write_verified(&reg(), 5, VerifyPolicy::with_retries(2, Backoff::Spin))
+            .unwrap_err();
+        assert_eq!(
+            mismatch(err),
+            WriteVerifyError {
+                offset: 4,
+                expected: 5,
+                actual: 0,
+                attempts: 3,
+            }
+        );
+        assert_eq!(sesh.writes, 3);
+
+        let err = sesh
+            .write_verified(&reg(), 5, VerifyPolicy::default())
+            .unwrap_err();
+        assert_eq!(mismatch(err).attempts, 1);
+    }
+
+    #[test]
+    fn succeeds_once_a_retry_latches() {
+        let mut sesh = Sticky::new(3);
+        let policy = VerifyPolicy::with_retries(5, Backoff::Fixed(Duration::from_millis(1)));
+        sesh.write_verified(&reg(), 5, policy).unwrap();
+        assert_eq!(sesh.writes, 3);
+        assert_eq!(sesh.read(&reg()).unwrap(), 5);
+    }
+
+    #[test]
+    fn too_few_retries_fail_before_the_write_latches() {
+        let mut sesh = Sticky::new(3);
+        let err = sesh
+            .write_verified(&reg(), 5, VerifyPolicy::with_retries(1, Backoff::Spin))
+            .unwrap_err();
+        assert_eq!(mismatch(err).attempts, 2);
+    }
+}
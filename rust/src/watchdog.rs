@@ -0,0 +1,406 @@
+//! Heartbeat and hardware watchdog support for long-running sessions.
+//!
+//! A background thread writes a heartbeat register every period so that
+//! hardware can detect a hung process. On drop the heartbeat stops and a
+//! safe-state sequence is applied. `SimWatchdog` simulates such hardware.
+
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::safe_state::{report_to_stderr, Reporter, Sequence};
+use crate::session::SimSesh;
+use crate::shared::SharedSesh;
+use crate::traits::{Readable, Session, Writable};
+use crate::{FpgaApiError, FpgaApiResult};
+
+struct Heartbeat {
+    stop: mpsc::Sender<()>,
+    thread: JoinHandle<FpgaApiResult<()>>,
+}
+
+/// Heartbeat and watchdog control for a shared session.
+pub struct Watchdog<S: Session + Send + 'static> {
+    sesh: SharedSesh<S>,
+    arm: Sequence<S>,
+    disarm: Sequence<S>,
+    safe_state: Sequence<S>,
+    report: Reporter,
+    heartbeat: Option<Heartbeat>,
+}
+impl<S: Session + Send + 'static> Watchdog<S> {
+    /// Watchdog that applies `safe_state` when dropped.
+    pub fn new(sesh: SharedSesh<S>, safe_state: Sequence<S>) -> Self {
+        Self {
+            sesh,
+            arm: Sequence::new(),
+            disarm: Sequence::new(),
+            safe_state,
+            report: Box::new(report_to_stderr),
+            heartbeat: None,
+        }
+    }
+    /// Report heartbeat and safe-state failures on drop with `f` instead of
+    /// printing them to stderr. See `safe_state::SafeSesh::new`.
+    pub fn on_drop_error<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(&FpgaApiError) + Send + 'static,
+    {
+        self.report = Box::new(f);
+        self
+    }
+    /// Register writes that arm the hardware watchdog.
+    pub fn arm_sequence(mut self, arm: Sequence<S>) -> Self {
+        self.arm = arm;
+        self
+    }
+    /// Register writes that disarm the hardware watchdog.
+    pub fn disarm_sequence(mut self, disarm: Sequence<S>) -> Self {
+        self.disarm = disarm;
+        self
+    }
+    /// Arm the hardware watchdog.
+    pub fn arm(&self) -> FpgaApiResult<()> {
+        self.sesh.with(|sesh| self.arm.run(sesh))
+    }
+    /// Disarm the hardware watchdog.
+    pub fn disarm(&self) -> FpgaApiResult<()> {
+        self.sesh.with(|sesh| self.disarm.run(sesh))
+    }
+    /// Write `beat()` to `resource` every `period` from a background thread,
+    /// starting immediately. The thread stops at the first failed write; the
+    /// failure is returned by `stop_heartbeat`.
+    pub fn start_heartbeat<R, F>(
+        &mut self,
+        resource: &R,
+        period: Duration,
+        mut beat: F,
+    ) -> FpgaApiResult<()>
+    where
+        R: Writable + Clone + Send + 'static,
+        F: FnMut() -> R::Value + Send + 'static,
+    {
+        if self.heartbeat.is_some() {
+            return Err(FpgaApiError::from("Heartbeat is already running!"));
+        }
+        let (stop, rx) = mpsc::channel();
+        let sesh = self.sesh.clone();
+        let resource = resource.clone();
+        let thread = std::thread::Builder::new()
+            .name("fpga-heartbeat".to_string())
+            .spawn(move || loop {
+                sesh.write(&resource, beat())?;
+                match rx.recv_timeout(period) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    // Stop requested or the watchdog was dropped.
+                    _ => return Ok(()),
+                }
+            })?;
+        self.heartbeat = Some(Heartbeat { stop, thread });
+        Ok(())
+    }
+    /// Stop the heartbeat thread and return the error that ended it, if any.
+    pub fn stop_heartbeat(&mut self) -> FpgaApiResult<()> {
+        match self.heartbeat.take() {
+            Some(Heartbeat { stop, thread }) => {
+                let _ = stop.send(());
+                thread
+                    .join()
+                    .map_err(|_| FpgaApiError::from("Heartbeat thread panicked!"))?
+            }
+            None => Ok(()),
+        }
+    }
+    /// Whether the heartbeat thread is still beating.
+    pub fn is_beating(&self) -> bool {
+        self.heartbeat
+            .as_ref()
+            .is_some_and(|h| !h.thread.is_finished())
+    }
+}
+impl<S: Session + Send + 'static> Drop for Watchdog<S> {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop_heartbeat() {
+            (self.report)(&e);
+        }
+        // A thread that panicked while holding the session is exactly when
+        // the safe state matters most, so a poisoned lock is not a reason to
+        // skip it.
+        let result = self.safe_state.run_all(&mut self.sesh.lock_poisoned());
+        if let Err(e) = result {
+            (self.report)(&e);
+        }
+    }
+}
+
+/// Time source of a `SimWatchdog`: the system clock, or a manual clock that
+/// only moves on `advance`, for tests that must not depend on scheduling.
+#[derive(Clone)]
+pub struct SimClock {
+    start: Instant,
+    // Time since `start`, `None` for the system clock.
+    manual: Option<Arc<Mutex<Duration>>>,
+}
+impl SimClock {
+    /// The system clock.
+    pub fn system() -> Self {
+        Self {
+            start: Instant::now(),
+            manual: None,
+        }
+    }
+    /// A clock that stands still until advanced. Clones share the time.
+    pub fn manual() -> Self {
+        Self {
+            start: Instant::now(),
+            manual: Some(Arc::new(Mutex::new(Duration::from_secs(0)))),
+        }
+    }
+    /// Move a manual clock forward. Does nothing to the system clock.
+    pub fn advance(&self, by: Duration) {
+        if let Some(t) = &self.manual {
+            *t.lock().unwrap_or_else(|e| e.into_inner()) += by;
+        }
+    }
+    /// Current time on this clock.
+    pub fn now(&self) -> Instant {
+        match &self.manual {
+            Some(t) => self.start + *t.lock().unwrap_or_else(|e| e.into_inner()),
+            None => Instant::now(),
+        }
+    }
+}
+
+/// Register block with a simulated hardware watchdog, for testing heartbeats
+/// without hardware.
+///
+/// Writing a nonzero value to the enable register arms the watchdog. While
+/// armed, it trips if the heartbeat register is not written for longer than
+/// `timeout`. A tripped watchdog stays tripped.
+pub struct SimWatchdog {
+    sesh: SimSesh,
+    heartbeat: usize,
+    enable: usize,
+    timeout: Duration,
+    clock: SimClock,
+    // Time of the last beat (or arming), `None` while disarmed.
+    last_beat: Option<Instant>,
+    tripped: bool,
+}
+impl SimWatchdog {
+    /// Zero-initialized register block of `len` bytes with the heartbeat and
+    /// enable registers at the given byte offsets.
+    pub fn new(len: usize, heartbeat: usize, enable: usize, timeout: Duration) -> Self {
+        Self {
+            sesh: SimSesh::new(len),
+            heartbeat,
+            enable,
+            timeout,
+            clock: SimClock::system(),
+            last_beat: None,
+            tripped: false,
+        }
+    }
+    /// Measure the heartbeat deadline on `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SimClock) -> Self {
+        self.clock = clock;
+        self
+    }
+    /// Raw register block.
+    pub fn memory(&self) -> &[u8] {
+        self.sesh.memory()
+    }
+    /// Whether the watchdog is armed.
+    pub fn is_armed(&self) -> bool {
+        self.last_beat.is_some()
+    }
+    /// Whether the heartbeat deadline was missed while armed.
+    pub fn is_tripped(&self) -> bool {
+        let now = self.clock.now();
+        self.tripped || self.last_beat.is_some_and(|t| now - t > self.timeout)
+    }
+}
+impl Session for SimWatchdog {
+    fn read<R: Readable>(&self, resource: &R) -> FpgaApiResult<R::Value> {
+        self.sesh.read(resource)
+    }
+    fn write<R: Writable>(&mut self, resource: &R, val: R::Value) -> FpgaApiResult<()> {
+        // A late beat does not undo a missed deadline.
+        self.tripped = self.is_tripped();
+        self.sesh.write(resource, val)?;
+        let offset = resource.byte_offset();
+        if offset == self.enable {
+            let value = &self.sesh.memory()[offset..offset + resource.size_in_bytes()];
+            let now = self.clock.now();
+            self.last_beat = value.iter().any(|&b| b != 0).then_some(now);
+        } else if offset == self.heartbeat && self.is_armed() {
+            self.last_beat = Some(self.clock.now());
+        }
+        Ok(())
+    }
+}
+impl Drop for SimWatchdog {
+    fn drop(&mut self) {
+        // No hardware to put into a safe state.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::Resource;
+    use crate::traits::{ReadWrite, WriteOnly};
+
+    const TIMEOUT: Duration = Duration::from_millis(200);
+
+    fn heartbeat() -> Resource<u32, WriteOnly> {
+        Resource::new("heartbeat", 0)
+    }
+    fn beats() -> Resource<u32, ReadWrite> {
+        Resource::new("beats", 0)
+    }
+    fn enable() -> Resource<u32, ReadWrite> {
+        Resource::new("enable", 4)
+    }
+    fn output() -> Resource<u32, ReadWrite> {
+        Resource::new("output", 8)
+    }
+
+    /// Armed watchdog on a manual clock whose safe state disables the
+    /// output.
+    fn watchdog() -> (SharedSesh<SimWatchdog>, Watchdog<SimWatchdog>, SimClock) {
+        let clock = SimClock::manual();
+        let sim = SimWatchdog::new(12, 0, 4, TIMEOUT).with_clock(clock.clone());
+        let sesh = SharedSesh::new(sim);
+        sesh.write(&output(), 1).unwrap();
+        let watchdog = Watchdog::new(sesh.clone(), Sequence::new().write(&output(), 0))
+            .arm_sequence(Sequence::new().write(&enable(), 1))
+            .disarm_sequence(Sequence::new().write(&enable(), 0));
+        watchdog.arm().unwrap();
+        (sesh, watchdog, clock)
+    }
+
+    fn tripped(sesh: &SharedSesh<SimWatchdog>) -> bool {
+        sesh.lock().unwrap().is_tripped()
+    }
+
+    /// Wait (on the system clock) until the heartbeat thread has written a
+    /// beat after `after`.
+    fn next_beat(sesh: &SharedSesh<SimWatchdog>, after: u32) -> u32 {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let beat = sesh.read(&beats()).unwrap();
+            if beat > after {
+                return beat;
+            }
+            assert!(Instant::now() < deadline, "heartbeat thread stalled");
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn sim_watchdog_trips_without_beats() {
+        let (sesh, watchdog, clock) = watchdog();
+        assert!(sesh.lock().unwrap().is_armed());
+        clock.advance(TIMEOUT);
+        assert!(!tripped(&sesh));
+        clock.advance(Duration::from_millis(1));
+        assert!(tripped(&sesh));
+        // Disarming or beating late does not reset it.
+        watchdog.disarm().unwrap();
+        sesh.write(&heartbeat(), 1).unwrap();
+        assert!(tripped(&sesh));
+    }
+
+    #[test]
+    fn disarmed_sim_watchdog_does_not_trip() {
+        let (sesh, watchdog, clock) = watchdog();
+        watchdog.disarm().unwrap();
+        clock.advance(TIMEOUT * 10);
+        assert!(!tripped(&sesh));
+    }
+
+    #[test]
+    fn beating_keeps_it_alive() {
+        let (sesh, mut watchdog, clock) = watchdog();
+        let mut count = 0u32;
+        watchdog
+            .start_heartbeat(&heartbeat(), Duration::from_millis(1), move || {
+                count += 1;
+                count
+            })
+            .unwrap();
+        let mut beat = next_beat(&sesh, 0);
+        for _ in 0..10 {
+            clock.advance(TIMEOUT / 2);
+            beat = next_beat(&sesh, beat);
+            assert!(!tripped(&sesh));
+        }
+        assert!(watchdog.is_beating());
+        assert!(watchdog
+            .start_heartbeat(&heartbeat(), TIMEOUT, || 0)
+            .is_err());
+        watchdog.stop_heartbeat().unwrap();
+        assert!(!watchdog.is_beating());
+        assert!(!tripped(&sesh));
+    }
+
+    #[test]
+    fn stop_heartbeat_trips_it() {
+        let (sesh, mut watchdog, clock) = watchdog();
+        watchdog
+            .start_heartbeat(&heartbeat(), Duration::from_millis(1), || 1)
+            .unwrap();
+        next_beat(&sesh, 0);
+        watchdog.stop_heartbeat().unwrap();
+        assert!(!tripped(&sesh));
+        clock.advance(TIMEOUT * 2);
+        assert!(tripped(&sesh));
+    }
+
+    #[test]
+    fn drop_applies_safe_state() {
+        let (sesh, mut watchdog, _) = watchdog();
+        watchdog
+            .start_heartbeat(&heartbeat(), Duration::from_millis(1), || 1)
+            .unwrap();
+        assert_eq!(sesh.read(&output()).unwrap(), 1);
+        drop(watchdog);
+        assert_eq!(sesh.read(&output()).unwrap(), 0);
+    }
+
+    #[test]
+    fn drop_reports_errors_to_the_hook() {
+        let sesh = SharedSesh::new(SimWatchdog::new(12, 0, 4, TIMEOUT));
+        sesh.write(&output(), 1).unwrap();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = reports.clone();
+        let outside = Resource::<u32, ReadWrite>::new("outside", 12);
+        let watchdog = Watchdog::new(
+            sesh.clone(),
+            Sequence::new().write(&outside, 0).write(&output(), 0),
+        )
+        .on_drop_error(move |e| sink.lock().unwrap().push(e.to_string()));
+        drop(watchdog);
+        assert_eq!(sesh.read(&output()).unwrap(), 0);
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].contains("step 0"));
+    }
+
+    #[test]
+    fn drop_applies_safe_state_after_a_panic_poisoned_the_session() {
+        let (sesh, watchdog, _) = watchdog();
+        let poisoner = sesh.clone();
+        let panicked = std::thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("panic while holding the session");
+        })
+        .join();
+        assert!(panicked.is_err());
+        assert!(sesh.lock().is_err());
+        drop(watchdog);
+        assert_eq!(sesh.lock_poisoned().read(&output()).unwrap(), 0);
+    }
+}